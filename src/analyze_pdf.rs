@@ -1,16 +1,129 @@
 //! This module interprets the parsed PDF data into a Daylio struct.
 
-use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike};
+use std::cmp::Reverse;
+use std::fmt::Display;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use color_eyre::{eyre, Result};
+use color_eyre::eyre::{ContextCompat, WrapErr};
+
+use crate::{apply_time_zone, daylio, Daylio, merge, NUMBER_OF_PREDEFINED_MOODS, Tz};
+use crate::locale::PdfLocale;
+use crate::parse_pdf::{DayEntry, ParsedPdf, PdfDiagnostic, StatLine};
+
+/// How to find note titles. The PDF only contains text, where titles look like any other line
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TitleHeuristic {
+    /// Keep the title in the note
+    #[default]
+    Never,
+    /// The first line is the title, when the note has more than one line
+    FirstLine,
+    /// The first line is the title when it looks like one: short, not ending a sentence, and not continued on the next line
+    Guess,
+}
+
+/// Options to read PDF exports
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PdfOptions {
+    /// Language of the PDF, detected if `None`. Experimental languages must be given
+    pub locale: Option<&'static PdfLocale>,
+    pub titles: TitleHeuristic,
+    /// Skip the entries that cannot be read, instead of failing
+    pub lenient: bool,
+    /// Time zone of the entries, which only have a local time in the PDF. Offsets are left at 0 if `None`
+    pub time_zone: Option<Tz>,
+}
+
+/// Result of reading a PDF export
+#[derive(Debug, Clone)]
+pub struct PdfImport {
+    pub daylio: Daylio,
+    /// Language of the PDF, detected or chosen in the options
+    pub locale: &'static PdfLocale,
+    /// Entries that were skipped, in lenient mode
+    pub diagnostics: Vec<PdfDiagnostic>,
+    /// Comparison of the imported entries with the statistics page
+    pub verification: PdfVerification,
+    /// Tag lines that can be read in several ways
+    pub ambiguous_tags: Vec<TagAmbiguity>,
+}
+
+/// Whether a line of the statistics page counts a mood or a tag
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StatKind {
+    Mood,
+    Tag,
+}
+
+/// A count of the statistics page that differs from the imported entries
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StatDrift {
+    pub kind: StatKind,
+    pub name: String,
+    /// Count printed in the statistics page
+    pub expected: u32,
+    /// Number of imported entries with this mood or tag
+    pub found: u32,
+}
+
+impl Display for StatDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            StatKind::Mood => "mood",
+            StatKind::Tag => "tag",
+        };
+        write!(
+            f,
+            "{kind} `{}`: {} in the statistics, {} imported",
+            self.name, self.expected, self.found
+        )
+    }
+}
 
-use crate::{daylio, Daylio, merge, NUMBER_OF_PREDEFINED_MOODS};
-use crate::parse_pdf::{DayEntry, ParsedPdf, StatLine};
+/// Comparison of the imported entries with the counts of the statistics page.
+///
+/// Moods count lost entries, such as the ones skipped in lenient mode or split across pages.
+/// Tags also count the ones that were not recognized in the notes.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PdfVerification {
+    /// Entries counted by the statistics page
+    pub expected_entries: u32,
+    pub found_entries: u32,
+    pub drifts: Vec<StatDrift>,
+}
+
+impl PdfVerification {
+    /// Whether every count of the statistics page matches the imported entries
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.drifts.is_empty() && self.expected_entries == self.found_entries
+    }
+}
+
+impl Display for PdfVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} entries in the statistics, {} imported",
+            self.expected_entries, self.found_entries
+        )?;
+        for drift in &self.drifts {
+            write!(f, "\n{drift}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Longest line that [`TitleHeuristic::Guess`] takes for a title
+const MAX_GUESSED_TITLE_LEN: usize = 60;
 
 #[derive(Debug, PartialEq, Clone, Default)]
 struct ProcessedDayEntry {
     date: NaiveDateTime,
     mood: i64,
     tags: Vec<i64>,
+    note_title: String,
     note: String,
 }
 
@@ -49,9 +162,11 @@ fn convert_24_hour_to_12_hour(time_str: &str) -> Result<String> {
         date_parts[2]
     } else {
         // 24h clock
-        let hour_int = hour.parse::<u8>().unwrap();
+        let hour_int = hour.parse::<u8>()?;
         if hour_int > 12 {
             hour = (hour_int - 12).to_string();
+        }
+        if hour_int >= 12 {
             "pm"
         } else {
             "am"
@@ -59,17 +174,17 @@ fn convert_24_hour_to_12_hour(time_str: &str) -> Result<String> {
     };
 
     // sanitize hour
-    if hour == "00" {
+    if hour == "00" || hour == "0" {
         hour = "12".to_owned();
     }
 
     Ok(format!("{hour} {minute} {am_pm}"))
 }
 
-fn parse_date(entry: &DayEntry) -> Result<NaiveDateTime> {
+/// Example: Sunday 8 53 PM
+pub(crate) fn parse_time(day_hour: &str) -> Result<NaiveTime> {
     // skip the day of the week
-    let mut time_str = entry
-        .day_hour
+    let mut time_str = day_hour
         .split_whitespace()
         .skip(1)
         .collect::<Vec<_>>()
@@ -79,54 +194,190 @@ fn parse_date(entry: &DayEntry) -> Result<NaiveDateTime> {
     time_str = time_str.replace(':', " ");
     time_str = convert_24_hour_to_12_hour(&time_str)?;
 
-    let time = NaiveTime::parse_from_str(&time_str, "%l %M %p")?;
+    Ok(NaiveTime::parse_from_str(&time_str, "%l %M %p")?)
+}
+
+fn parse_date(entry: &DayEntry) -> Result<NaiveDateTime> {
+    let time = parse_time(&entry.day_hour)?;
     Ok(NaiveDateTime::new(entry.date, time))
 }
 
+/// Tags that can be read in several ways, the longest tags were chosen
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TagAmbiguity {
+    pub date: NaiveDate,
+    /// Day and time of the entry, as printed in the PDF
+    pub day_hour: String,
+    /// Column of the tag line
+    pub text: String,
+    pub chosen: Vec<String>,
+    pub alternative: Vec<String>,
+}
+
+impl Display for TagAmbiguity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: `{}` read as {:?}, could also be {:?}",
+            self.date, self.day_hour, self.text, self.chosen, self.alternative
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+struct ExtractedTags {
+    note: String,
+    tags: Vec<String>,
+    ambiguities: Vec<TagAmbiguity>,
+}
+
+/// Ways to read a column as a list of tags separated by spaces, starting with the longest tags.
+/// Stops after `limit` ways, as only the first one is kept.
+fn split_tags<'a>(column: &str, tags: &[&'a str], limit: usize) -> Vec<Vec<&'a str>> {
+    if column.is_empty() {
+        return vec![vec![]];
+    }
+
+    let mut ways = Vec::new();
+    // tags are sorted from the longest
+    for tag in tags {
+        let Some(rest) = column.strip_prefix(tag) else {
+            continue;
+        };
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            continue;
+        }
+        for mut way in split_tags(rest.trim_start(), tags, limit - ways.len()) {
+            way.insert(0, *tag);
+            ways.push(way);
+            if ways.len() == limit {
+                return ways;
+            }
+        }
+    }
+    ways
+}
+
 /// Extracts tags from the note, and returns the note with the tags removed.
-fn extract_tags(entry: &DayEntry, stats: &Vec<StatLine>) -> (String, Vec<String>) {
-    let mut entry_tags = Vec::new();
-
-    let mut last_tag_line = None;
-    for (i, line) in entry.note.iter().enumerate() {
-        for tag in stats {
-            // tag comparison is case sensitive
-            if line.contains(&tag.name) {
-                entry_tags.push(tag.name.clone());
-                last_tag_line = Some(i);
+///
+/// Tags are on the first lines of the note, in columns separated by several spaces.
+/// A line is only made of tags when each of its columns is, so a note that quotes a tag is kept.
+fn extract_tags(entry: &DayEntry, stats: &[StatLine]) -> ExtractedTags {
+    let mut tags = stats
+        .iter()
+        .map(|tag| tag.name.as_str())
+        .collect::<Vec<_>>();
+    tags.sort_by_key(|tag| Reverse(tag.len()));
+
+    let mut extracted = ExtractedTags::default();
+    let mut tag_lines = 0;
+    'lines: for line in &entry.note {
+        let mut line_tags = Vec::new();
+        let mut ambiguities = Vec::new();
+        // tag comparison is case sensitive
+        for column in line.split("  ").map(str::trim).filter(|c| !c.is_empty()) {
+            let mut ways = split_tags(column, &tags, 2).into_iter();
+            let Some(chosen) = ways.next() else {
+                break 'lines;
+            };
+            if let Some(alternative) = ways.next() {
+                ambiguities.push(TagAmbiguity {
+                    date: entry.date,
+                    day_hour: entry.day_hour.clone(),
+                    text: column.to_owned(),
+                    chosen: chosen.iter().map(ToString::to_string).collect(),
+                    alternative: alternative.iter().map(ToString::to_string).collect(),
+                });
             }
+            line_tags.extend(chosen.into_iter().map(str::to_owned));
         }
-        if last_tag_line != Some(i) {
+        if line_tags.is_empty() {
             break;
         }
+
+        extracted.tags.extend(line_tags);
+        extracted.ambiguities.extend(ambiguities);
+        tag_lines += 1;
     }
 
-    let note = if let Some(last_tag_line) = last_tag_line {
-        let mut note = entry.note.clone();
-        note.drain(..=last_tag_line);
-        note
-    } else {
-        entry.note.clone()
+    // in the order of the statistics page rather than of the columns, so ids do not depend on the layout
+    extracted
+        .tags
+        .sort_by_key(|tag| stats.iter().position(|stat| stat.name == *tag));
+    extracted.note = entry.note[tag_lines..].join("\n");
+    extracted
+}
+
+/// Splits the title from the note, when the heuristic finds one
+fn split_title(note: &str, heuristic: TitleHeuristic) -> (String, String) {
+    let Some((first, rest)) = note.split_once('\n') else {
+        return (String::new(), note.to_owned());
     };
 
-    (note.join("\n"), entry_tags)
+    let is_title = match heuristic {
+        TitleHeuristic::Never => false,
+        TitleHeuristic::FirstLine => true,
+        TitleHeuristic::Guess => {
+            let ends_sentence = first.ends_with(['.', ',', ';', ':']);
+            let continued = rest.starts_with(char::is_lowercase);
+            first.chars().count() <= MAX_GUESSED_TITLE_LEN && !ends_sentence && !continued
+        }
+    };
+
+    if is_title {
+        (first.to_owned(), rest.to_owned())
+    } else {
+        (String::new(), note.to_owned())
+    }
 }
 
-fn predefined_mood_idx(custom_name: &str) -> Option<i64> {
-    match custom_name.to_lowercase().as_ref() {
-        "super" | "rad" => Some(1),
-        "bien" | "good" => Some(2),
-        "mouais" | "meh" => Some(3),
-        "mauvais" | "bad" => Some(4),
-        "horrible" | "awful" => Some(5),
-        _ => None,
+/// Counts the entries of each mood and tag, to compare them with the statistics page
+fn verify(parsed: &ParsedPdf) -> PdfVerification {
+    let moods = parsed
+        .day_entries
+        .iter()
+        .map(|entry| entry.mood.to_lowercase())
+        .collect::<Vec<_>>();
+    let tags = parsed
+        .day_entries
+        .iter()
+        .map(|entry| extract_tags(entry, &parsed.stats).tags)
+        .collect::<Vec<_>>();
+    let count = |found: usize| u32::try_from(found).unwrap_or(u32::MAX);
+
+    let mut verification = PdfVerification {
+        found_entries: count(parsed.day_entries.len()),
+        ..Default::default()
+    };
+    for stat in &parsed.stats {
+        // the statistics page is lowercase, unlike the moods of the entries
+        let name = stat.name.to_lowercase();
+        let is_mood = moods.contains(&name) || parsed.locale.predefined_mood_idx(&name).is_some();
+        let (kind, found) = if is_mood {
+            verification.expected_entries += stat.count;
+            let found = moods.iter().filter(|mood| **mood == name).count();
+            (StatKind::Mood, found)
+        } else {
+            let found = tags.iter().filter(|tags| tags.contains(&stat.name)).count();
+            (StatKind::Tag, found)
+        };
+
+        if count(found) != stat.count {
+            verification.drifts.push(StatDrift {
+                kind,
+                name: stat.name.clone(),
+                expected: stat.count,
+                found: count(found),
+            });
+        }
     }
+    verification
 }
 
-fn update_mood_category(moods: &mut [Mood]) {
+fn update_mood_category(moods: &mut [Mood], locale: &PdfLocale) {
     let mut prev_id = None;
     for mood in moods {
-        if let Some(idx) = predefined_mood_idx(&mood.name) {
+        if let Some(idx) = locale.predefined_mood_idx(&mood.name) {
             mood.id = idx;
             mood.predefined = true;
             prev_id = Some(idx);
@@ -140,7 +391,7 @@ fn list_tags_and_moods(parsed: &ParsedPdf) -> (Vec<Tag>, Vec<Mood>) {
     let mut tags: Vec<Tag> = Vec::new();
 
     for entry in &parsed.day_entries {
-        let (_, entry_tags) = extract_tags(entry, &parsed.stats);
+        let entry_tags = extract_tags(entry, &parsed.stats).tags;
         if !moods.iter().any(|m| m.name == entry.mood) {
             moods.push(Mood {
                 id: moods.len() as i64 + NUMBER_OF_PREDEFINED_MOODS,
@@ -163,42 +414,55 @@ fn list_tags_and_moods(parsed: &ParsedPdf) -> (Vec<Tag>, Vec<Mood>) {
     // sort moods according to the order they appear in the PDF
     let mut moods: Vec<Mood> = moods.into_iter().collect();
     moods.sort_by_key(|mood| parsed.stats.iter().position(|stat| stat.name == mood.name));
-    update_mood_category(&mut moods);
+    update_mood_category(&mut moods, parsed.locale);
 
     (tags.into_iter().collect(), moods)
 }
 
-impl From<ParsedPdf> for ProcessedPdf {
-    fn from(parsed: ParsedPdf) -> Self {
-        let (tags, moods) = list_tags_and_moods(&parsed);
+impl ProcessedPdf {
+    /// Entries must have been checked by the parser, so this only fails on a bug
+    pub(crate) fn new(parsed: &ParsedPdf, titles: TitleHeuristic) -> Result<Self> {
+        let (tags, moods) = list_tags_and_moods(parsed);
 
         let day_entries = parsed
             .day_entries
-            .into_iter()
+            .iter()
             .map(|entry| {
-                let date = parse_date(&entry).unwrap();
-                let (note, entry_tags) = extract_tags(&entry, &parsed.stats);
+                let date = parse_date(entry)
+                    .wrap_err_with(|| format!("Invalid time: {}", entry.day_hour))?;
+                let extracted = extract_tags(entry, &parsed.stats);
+                let (note_title, note) = split_title(&extracted.note, titles);
 
-                let entry_mood = moods.iter().find(|x| x.name == entry.mood).unwrap().id;
-                let entry_tags = entry_tags
+                let entry_mood = moods
                     .iter()
-                    .map(|x| tags.iter().find(|y| y.name == *x).unwrap().id)
-                    .collect();
-
-                ProcessedDayEntry {
+                    .find(|x| x.name == entry.mood)
+                    .wrap_err_with(|| format!("Unknown mood: {}", entry.mood))?
+                    .id;
+                let entry_tags = extracted
+                    .tags
+                    .iter()
+                    .map(|x| {
+                        let tag = tags.iter().find(|y| y.name == *x);
+                        tag.map(|tag| tag.id)
+                            .wrap_err_with(|| format!("Unknown tag: {x}"))
+                    })
+                    .collect::<Result<_>>()?;
+
+                Ok(ProcessedDayEntry {
                     date,
                     mood: entry_mood,
                     tags: entry_tags,
+                    note_title,
                     note,
-                }
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        ProcessedPdf {
+        Ok(ProcessedPdf {
             day_entries,
             moods,
             tags,
-        }
+        })
     }
 }
 
@@ -240,6 +504,7 @@ impl From<ProcessedDayEntry> for daylio::DayEntry {
             year: i64::from(entry.date.year()),
             datetime: entry.date.and_utc().timestamp_millis(),
             mood: entry.mood,
+            note_title: entry.note_title,
             note: entry.note,
             tags: entry.tags,
             ..Default::default()
@@ -247,6 +512,28 @@ impl From<ProcessedDayEntry> for daylio::DayEntry {
     }
 }
 
+impl PdfImport {
+    pub(crate) fn new(parsed: &ParsedPdf, options: &PdfOptions) -> Result<Self> {
+        let processed = ProcessedPdf::new(parsed, options.titles)?;
+        let mut daylio = processed.into();
+        if let Some(time_zone) = options.time_zone {
+            apply_time_zone(&mut daylio, time_zone);
+        }
+
+        Ok(PdfImport {
+            daylio,
+            locale: parsed.locale,
+            diagnostics: parsed.diagnostics.clone(),
+            verification: verify(parsed),
+            ambiguous_tags: parsed
+                .day_entries
+                .iter()
+                .flat_map(|entry| extract_tags(entry, &parsed.stats).ambiguities)
+                .collect(),
+        })
+    }
+}
+
 impl From<ProcessedPdf> for Daylio {
     fn from(pdf: ProcessedPdf) -> Self {
         merge(
@@ -284,6 +571,21 @@ mod tests {
         assert_eq!(date.second(), 0);
     }
 
+    #[test]
+    fn test_parse_date_24_hour() {
+        let hours = [("00 15", 0), ("09 59", 9), ("12 05", 12), ("22 20", 22)];
+
+        for (time, hour) in hours {
+            let entry = DayEntry {
+                date: NaiveDate::from_ymd_opt(2022, 8, 2).unwrap(),
+                day_hour: format!("mardi {time}"),
+                mood: String::new(),
+                note: vec![],
+            };
+            assert_eq!(parse_date(&entry).unwrap().hour(), hour, "{time}");
+        }
+    }
+
     impl StatLine {
         fn with_name(name: &str) -> Self {
             StatLine {
@@ -315,7 +617,11 @@ mod tests {
             StatLine::with_name("A tag, on another line"),
             StatLine::with_name("A tag that does not matches CASE"),
         ];
-        let (note, tags) = extract_tags(&entry, &stats);
+        let ExtractedTags {
+            note,
+            tags,
+            ambiguities,
+        } = extract_tags(&entry, &stats);
 
         let expected_note = [
             "A tag that does not matches case".to_owned(),
@@ -332,11 +638,111 @@ mod tests {
 
         assert_eq!(note, expected_note);
         assert_eq!(tags, expected_tags);
+        assert_eq!(ambiguities, vec![]);
+    }
+
+    fn note_entry(note: &[&str]) -> DayEntry {
+        DayEntry {
+            date: NaiveDate::from_ymd_opt(2022, 9, 2).unwrap(),
+            day_hour: "Friday 8 45 PM".to_owned(),
+            mood: String::new(),
+            note: note.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_extract_overlapping_tags() {
+        let stats = ["Work", "Homework", "Work out"].map(StatLine::with_name);
+
+        let entry = note_entry(&["Homework    Work out", "Work", "Working late"]);
+        let extracted = extract_tags(&entry, &stats);
+        assert_eq!(extracted.tags, ["Work", "Homework", "Work out"]);
+        assert_eq!(extracted.note, "Working late");
+        assert_eq!(extracted.ambiguities, vec![]);
+
+        // columns squeezed by the layout are split on single spaces, starting with the longest tags
+        let entry = note_entry(&["Homework Work out  Work"]);
+        let extracted = extract_tags(&entry, &stats);
+        assert_eq!(extracted.tags, ["Work", "Homework", "Work out"]);
+    }
+
+    #[test]
+    fn test_extract_tags_quoted_in_note() {
+        let stats = ["Work", "sport"].map(StatLine::with_name);
+
+        let entry = note_entry(&["Work  sport", "Homework was long", "sport   was fun"]);
+        let extracted = extract_tags(&entry, &stats);
+        assert_eq!(extracted.tags, ["Work", "sport"]);
+        assert_eq!(extracted.note, "Homework was long\nsport   was fun");
+
+        // a note can start with a tag name when the entry has no tags
+        let entry = note_entry(&["Work was fine, then sport"]);
+        let extracted = extract_tags(&entry, &stats);
+        assert_eq!(extracted.tags, Vec::<String>::new());
+        assert_eq!(extracted.note, "Work was fine, then sport");
+    }
+
+    #[test]
+    fn test_extract_ambiguous_tags() {
+        let stats = ["Work", "out", "Work out"].map(StatLine::with_name);
+
+        let entry = note_entry(&["Work out  out"]);
+        let extracted = extract_tags(&entry, &stats);
+        assert_eq!(extracted.tags, ["out", "Work out"]);
+        assert_eq!(
+            extracted.ambiguities,
+            [TagAmbiguity {
+                date: entry.date,
+                day_hour: entry.day_hour.clone(),
+                text: "Work out".to_owned(),
+                chosen: vec!["Work out".to_owned()],
+                alternative: vec!["Work".to_owned(), "out".to_owned()],
+            }]
+        );
+        assert_eq!(
+            extracted.ambiguities[0].to_string(),
+            r#"2022-09-02 Friday 8 45 PM: `Work out` read as ["Work out"], could also be ["Work", "out"]"#
+        );
+    }
+
+    #[test]
+    fn test_split_title() {
+        let notes = [
+            ("Note title\nNote body", "Note title", "Note body"),
+            ("Great day!\nWent to the beach", "Great day!", "Went to the beach"),
+            ("Only one line", "", "Only one line"),
+            ("Went to the beach.\nThen home", "", "Went to the beach.\nThen home"),
+            ("A sentence that goes\non the next line", "", "A sentence that goes\non the next line"),
+            (
+                "A line that is far too long to be a title, as titles are usually a few words\nBody",
+                "",
+                "A line that is far too long to be a title, as titles are usually a few words\nBody",
+            ),
+        ];
+
+        for (note, title, body) in notes {
+            assert_eq!(
+                split_title(note, TitleHeuristic::Guess),
+                (title.to_owned(), body.to_owned()),
+                "{note}"
+            );
+            assert_eq!(
+                split_title(note, TitleHeuristic::Never),
+                (String::new(), note.to_owned())
+            );
+        }
+
+        assert_eq!(
+            split_title("Went to the beach.\nThen home", TitleHeuristic::FirstLine),
+            ("Went to the beach.".to_owned(), "Then home".to_owned())
+        );
     }
 
     #[test]
     fn test_processed_pdf_from_parsed_pdf() {
         let parsed = ParsedPdf {
+            locale: PdfLocale::from_code("en").unwrap(),
+            diagnostics: vec![],
             day_entries: vec![
                 DayEntry {
                     date: NaiveDate::from_ymd_opt(2022, 9, 2).unwrap(),
@@ -377,18 +783,21 @@ mod tests {
                     date: parse_date(&parsed.day_entries[0]).unwrap(),
                     mood: 1,
                     tags: vec![],
+                    note_title: String::new(),
                     note: "This is a note".to_owned(),
                 },
                 ProcessedDayEntry {
                     date: parse_date(&parsed.day_entries[1]).unwrap(),
                     mood: 1,
                     tags: vec![],
+                    note_title: String::new(),
                     note: "This is a note²".to_owned(),
                 },
                 ProcessedDayEntry {
                     date: parse_date(&parsed.day_entries[2]).unwrap(),
                     mood: 2,
                     tags: vec![0, 1, 2],
+                    note_title: String::new(),
                     note: "Note title\nNote body".to_owned(),
                 },
             ],
@@ -422,8 +831,119 @@ mod tests {
             ],
         };
 
-        let processed = ProcessedPdf::from(parsed);
+        let processed = ProcessedPdf::new(&parsed, TitleHeuristic::Never).unwrap();
 
         assert_eq!(processed, expected);
     }
+
+    #[test]
+    fn test_verify() {
+        let entry = |mood: &str, note: &str| DayEntry {
+            date: NaiveDate::from_ymd_opt(2022, 9, 2).unwrap(),
+            day_hour: "Monday 8 45 PM".to_owned(),
+            mood: mood.to_owned(),
+            note: vec![note.to_owned()],
+        };
+        let stat = |name: &str, count: u32| StatLine {
+            name: name.to_owned(),
+            count,
+        };
+        let parsed = ParsedPdf {
+            locale: PdfLocale::from_code("en").unwrap(),
+            diagnostics: vec![],
+            day_entries: vec![
+                entry("RAD", "sport  work"),
+                entry("RAD", "work"),
+                entry("tired", "sport"),
+            ],
+            stats: vec![
+                stat("rad", 2),
+                stat("sport", 2),
+                stat("work", 3),
+                stat("tired", 1),
+                stat("meh", 1),
+                stat("reading", 1),
+            ],
+        };
+
+        let verification = verify(&parsed);
+        assert_eq!(
+            verification,
+            PdfVerification {
+                expected_entries: 4,
+                found_entries: 3,
+                drifts: vec![
+                    StatDrift {
+                        kind: StatKind::Tag,
+                        name: "work".to_owned(),
+                        expected: 3,
+                        found: 2,
+                    },
+                    StatDrift {
+                        kind: StatKind::Mood,
+                        name: "meh".to_owned(),
+                        expected: 1,
+                        found: 0,
+                    },
+                    StatDrift {
+                        kind: StatKind::Tag,
+                        name: "reading".to_owned(),
+                        expected: 1,
+                        found: 0,
+                    },
+                ],
+            }
+        );
+        assert!(!verification.is_complete());
+        assert_eq!(
+            verification.to_string(),
+            "4 entries in the statistics, 3 imported\n\
+             tag `work`: 3 in the statistics, 2 imported\n\
+             mood `meh`: 1 in the statistics, 0 imported\n\
+             tag `reading`: 1 in the statistics, 0 imported"
+        );
+    }
+
+    #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
+    fn test_verify_missing_entry() -> Result<()> {
+        let text = crate::pdf_text::extract_txt("tests/data/official/english.pdf".as_ref())?;
+        // drop the RAD entry of January 24, up to the next entry
+        let lines = text.lines().collect::<Vec<_>>();
+        let start = lines
+            .iter()
+            .position(|line| line.starts_with("January 24, 2023") && line.ends_with("RAD"))
+            .unwrap();
+        let end = start
+            + lines[start..]
+                .iter()
+                .position(|line| line.starts_with("January 11, 2023"))
+                .unwrap();
+        let text = [&lines[..start], &lines[end..]].concat().join("\n");
+
+        let parsed = crate::parse_pdf::parse_pdf_text(&text, None, false)?;
+        let drift = |kind, name: &str, expected| StatDrift {
+            kind,
+            name: name.to_owned(),
+            expected,
+            found: expected - 1,
+        };
+        assert_eq!(
+            verify(&parsed),
+            PdfVerification {
+                expected_entries: 5,
+                found_entries: 4,
+                drifts: vec![
+                    drift(StatKind::Mood, "rad", 1),
+                    drift(StatKind::Tag, "exercice", 1),
+                    drift(StatKind::Tag, "ménage", 2),
+                    drift(StatKind::Tag, "sport", 1),
+                    drift(StatKind::Tag, "famille", 1),
+                    drift(StatKind::Tag, "rendez vous", 1),
+                ],
+            }
+        );
+
+        Ok(())
+    }
 }
@@ -1,6 +1,8 @@
 use nanorand::{Rng, WyRand};
 
-use crate::Daylio;
+use serde_json::Value;
+
+use crate::{Daylio, Unconfirmed};
 
 fn rand_string(len: usize) -> String {
     let mut rng = WyRand::new();
@@ -11,6 +13,21 @@ fn rand_string(len: usize) -> String {
     s
 }
 
+/// Fields added by newer versions of the app are kept, but they may contain text as personal as the notes
+fn anonymize_other(other: &mut serde_json::Map<String, Value>) {
+    fn anonymize_value(value: &mut Value) {
+        match value {
+            Value::String(text) => *text = rand_string(text.chars().count()),
+            Value::Array(values) => values.iter_mut().for_each(anonymize_value),
+            Value::Object(map) => map.values_mut().for_each(anonymize_value),
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+    other.values_mut().for_each(anonymize_value);
+}
+
+/// Replaces the names and notes with random text.
+/// Photos and audio files are dropped, along with the references to them
 pub fn anonymize(daylio: &mut Daylio) {
     daylio
         .custom_moods
@@ -20,23 +37,78 @@ pub fn anonymize(daylio: &mut Daylio) {
         .for_each(|(i, mood)| {
             mood.custom_name = format!("Mood {} {}", i, rand_string(3));
         });
+    for mood in &mut daylio.custom_moods {
+        anonymize_other(&mut mood.other);
+    }
 
     for (i, tag) in daylio.tags.iter_mut().enumerate() {
         tag.name = format!("Tag {} {}", i, rand_string(3));
+        anonymize_other(&mut tag.other);
     }
 
     for (i, entry) in daylio.day_entries.iter_mut().enumerate() {
         entry.note = format!("Note {} {}", i, rand_string(3));
         entry.time_zone_offset = 0;
         entry.note_title = format!("Note title {} {}", i, rand_string(3));
+        entry.assets.clear();
+        anonymize_other(&mut entry.other);
     }
+    daylio.assets.clear();
+    daylio.metadata.number_of_photos = 0;
+    daylio.metadata.photos_size = 0;
 
     for (i, group) in daylio.tag_groups.iter_mut().enumerate() {
         group.name = format!("Group {} {}", i, rand_string(3));
+        anonymize_other(&mut group.other);
+    }
+
+    if let Some(goals) = daylio.goals.typed_mut() {
+        for (i, goal) in goals.iter_mut().enumerate() {
+            goal.name = format!("Goal {} {}", i, rand_string(3));
+        }
+    } else {
+        // their names cannot be found, they are dropped with their check-ins
+        daylio.goals = Unconfirmed::default();
+        daylio.goal_entries = Unconfirmed::default();
+        daylio.goal_success_weeks = Unconfirmed::default();
     }
 
     for (i, template) in daylio.writing_templates.iter_mut().enumerate() {
         template.body = format!("Template {} {}", i, rand_string(3));
         template.title = format!("Template title {} {}", i, rand_string(3));
+        anonymize_other(&mut template.other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Asset, DayEntry};
+
+    use super::*;
+
+    #[test]
+    fn test_anonymize_drops_photos() {
+        let mut daylio = Daylio {
+            day_entries: vec![DayEntry {
+                note: "Holidays".to_owned(),
+                assets: vec![1],
+                ..Default::default()
+            }],
+            assets: vec![Asset {
+                id: 1,
+                checksum: "aaaa".to_owned(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        daylio.metadata.number_of_photos = 1;
+        daylio.metadata.photos_size = 4;
+
+        anonymize(&mut daylio);
+        assert_ne!(daylio.day_entries[0].note, "Holidays");
+        assert!(daylio.day_entries[0].assets.is_empty());
+        assert!(daylio.assets.is_empty());
+        assert_eq!(daylio.metadata.number_of_photos, 0);
+        assert_eq!(daylio.metadata.photos_size, 0);
     }
 }
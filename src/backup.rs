@@ -0,0 +1,62 @@
+//! A full Daylio backup: the JSON data, and the photos and audio files stored next to it.
+
+use crate::Daylio;
+
+/// Folder of the backup archive where Daylio stores the asset files
+pub const ASSETS_DIR: &str = "assets/";
+
+/// A file from the `assets/` folder of a backup archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetFile {
+    /// Path inside the archive, such as `assets/photos/2022/8/<checksum>`
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+impl AssetFile {
+    /// Daylio names asset files after their checksum
+    #[must_use]
+    pub fn checksum(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Backup {
+    pub daylio: Daylio,
+    pub assets: Vec<AssetFile>,
+}
+
+impl From<Daylio> for Backup {
+    fn from(daylio: Daylio) -> Self {
+        Self {
+            daylio,
+            assets: vec![],
+        }
+    }
+}
+
+impl Backup {
+    /// Drops the files that are not referenced by any asset, and the duplicated files
+    pub fn retain_referenced_assets(&mut self) {
+        let mut seen = Vec::new();
+        let daylio = &self.daylio;
+        self.assets.retain(|file| {
+            let checksum = file.checksum().to_owned();
+            let referenced = daylio.assets.iter().any(|asset| asset.checksum == checksum);
+            if !referenced || seen.contains(&checksum) {
+                return false;
+            }
+            seen.push(checksum);
+            true
+        });
+    }
+
+    /// Recomputes the photo metadata from the files actually present
+    pub fn update_metadata(&mut self) {
+        self.daylio.metadata.number_of_entries = self.daylio.day_entries.len() as i64;
+        self.daylio.metadata.number_of_photos = self.assets.len() as i64;
+        self.daylio.metadata.photos_size =
+            self.assets.iter().map(|file| file.data.len() as i64).sum();
+    }
+}
@@ -0,0 +1,199 @@
+//! Entries of both files recorded at the same time, but with a different content.
+//! For example, a note edited on one device only, or a tag added afterward.
+
+use std::collections::HashMap;
+
+use serde_derive::Serialize;
+
+use crate::{DayEntry, Daylio};
+
+/// What to do with two entries recorded at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    KeepFirst,
+    KeepSecond,
+    KeepBoth,
+    /// Keep a single entry, with the tags of both and the notes concatenated
+    Union,
+}
+
+/// Asks the user which resolution to apply. An entry is `None` when its file deleted it, in merges with a common
+/// ancestor. The `Daylio` can be used to find the names of moods and tags
+pub type AskResolution<'a> =
+    &'a mut dyn FnMut(&Daylio, Option<&DayEntry>, Option<&DayEntry>) -> Resolution;
+
+#[derive(Default)]
+pub enum MergeStrategy<'a> {
+    /// Both entries are kept, and show up twice in the app
+    #[default]
+    KeepBoth,
+    PreferFirst,
+    /// Keep the entry from the most recent backup
+    PreferNewest,
+    Union,
+    Interactive(AskResolution<'a>),
+}
+
+impl MergeStrategy<'_> {
+    /// How to resolve two versions of an entry, `None` when a file deleted it
+    pub(crate) fn resolve(
+        &mut self,
+        daylio: &Daylio,
+        first: Option<&DayEntry>,
+        second: Option<&DayEntry>,
+        second_is_newer: bool,
+    ) -> Resolution {
+        match self {
+            MergeStrategy::KeepBoth => Resolution::KeepBoth,
+            MergeStrategy::PreferNewest if second_is_newer => Resolution::KeepSecond,
+            MergeStrategy::PreferFirst | MergeStrategy::PreferNewest => Resolution::KeepFirst,
+            MergeStrategy::Union => Resolution::Union,
+            MergeStrategy::Interactive(ask) => ask(daylio, first, second),
+        }
+    }
+}
+
+/// Two entries recorded at the same time, and how they were resolved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Conflict {
+    pub datetime: i64,
+    /// Names of the fields that differ between both entries
+    pub fields: Vec<&'static str>,
+    pub resolution: Resolution,
+}
+
+fn sorted(ids: &[i64]) -> Vec<i64> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids
+}
+
+fn differing_fields(first: &DayEntry, second: &DayEntry) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    if first.mood != second.mood {
+        fields.push("mood");
+    }
+    if first.note_title != second.note_title {
+        fields.push("note_title");
+    }
+    if first.note != second.note {
+        fields.push("note");
+    }
+    if sorted(&first.tags) != sorted(&second.tags) {
+        fields.push("tags");
+    }
+    if sorted(&first.assets) != sorted(&second.assets) {
+        fields.push("assets");
+    }
+    if first.time_zone_offset != second.time_zone_offset {
+        fields.push("time_zone_offset");
+    }
+    if first.other != second.other {
+        fields.push("other");
+    }
+    fields
+}
+
+fn concatenate(first: &mut String, second: &str, separator: &str) {
+    if first.is_empty() {
+        second.clone_into(first);
+    } else if !second.is_empty() && first != second {
+        first.push_str(separator);
+        first.push_str(second);
+    }
+}
+
+fn union(first: &mut DayEntry, second: &DayEntry) {
+    for tag in &second.tags {
+        if !first.tags.contains(tag) {
+            first.tags.push(*tag);
+        }
+    }
+    for asset in &second.assets {
+        if !first.assets.contains(asset) {
+            first.assets.push(*asset);
+        }
+    }
+    concatenate(&mut first.note_title, &second.note_title, " / ");
+    concatenate(&mut first.note, &second.note, "\n\n");
+    for (key, value) in &second.other {
+        if !first.other.contains_key(key) {
+            first.other.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+impl Daylio {
+    /// Resolves the entries recorded at the same time in both files.
+    /// The first `first_len` entries come from the first file, the other ones from the second file.
+    /// Entries that are exactly the same are left for `remove_duplicates`
+    pub(crate) fn resolve_conflicts(
+        &mut self,
+        first_len: usize,
+        second_is_newer: bool,
+        mut strategy: MergeStrategy,
+    ) -> Vec<Conflict> {
+        let mut by_datetime: HashMap<i64, Vec<usize>> = HashMap::new();
+        for (i, entry) in self.day_entries.iter().enumerate().take(first_len) {
+            by_datetime.entry(entry.datetime).or_default().push(i);
+        }
+
+        let mut pairs = Vec::new();
+        for j in first_len..self.day_entries.len() {
+            let second = &self.day_entries[j];
+            let Some(candidates) = by_datetime.get_mut(&second.datetime) else {
+                continue;
+            };
+            if candidates.iter().any(|&i| self.day_entries[i] == *second) {
+                continue; // exact duplicate
+            }
+            if !candidates.is_empty() {
+                pairs.push((candidates.remove(0), j));
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        let mut removed = vec![false; self.day_entries.len()];
+        for (i, j) in pairs {
+            let fields = differing_fields(&self.day_entries[i], &self.day_entries[j]);
+
+            let resolution = match &mut strategy {
+                // only the ids or the order of the tags differ, this is the same entry
+                _ if fields.is_empty() => Resolution::KeepFirst,
+                strategy => strategy.resolve(
+                    self,
+                    Some(&self.day_entries[i]),
+                    Some(&self.day_entries[j]),
+                    second_is_newer,
+                ),
+            };
+
+            match resolution {
+                Resolution::KeepFirst => removed[j] = true,
+                Resolution::KeepSecond => removed[i] = true,
+                Resolution::KeepBoth => {}
+                Resolution::Union => {
+                    let second = self.day_entries[j].clone();
+                    union(&mut self.day_entries[i], &second);
+                    removed[j] = true;
+                }
+            }
+
+            if !fields.is_empty() {
+                conflicts.push(Conflict {
+                    datetime: self.day_entries[i].datetime,
+                    fields,
+                    resolution,
+                });
+            }
+        }
+
+        let mut removed = removed.into_iter();
+        self.day_entries
+            .retain(|_| !removed.next().unwrap_or(false));
+
+        conflicts.sort_by_key(|conflict| conflict.datetime);
+        conflicts
+    }
+}
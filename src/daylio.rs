@@ -4,8 +4,12 @@ use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use serde_json::Value;
 
+use crate::OLDEST_VERSION;
+
 pub const NUMBER_OF_PREDEFINED_MOODS: i64 = 5;
 
+/// A backup, as stored in the `backup.daylio` archive.
+/// Each struct keeps the fields added by newer versions of the app in `other`, and writes them back unchanged
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Daylio {
@@ -16,19 +20,21 @@ pub struct Daylio {
     pub day_entries: Vec<DayEntry>,
     pub achievements: Vec<Achievement>,
     pub days_in_row_longest_chain: i64,
-    pub goals: Vec<Value>,
+    pub goals: Unconfirmed<Goal>,
     pub prefs: Vec<Pref>,
     #[serde(rename = "tag_groups")]
     pub tag_groups: Vec<TagGroup>,
     pub metadata: Metadata,
     pub mood_icons_pack_id: i64,
     pub preferred_mood_icons_ids_for_mood_ids_for_icons_pack: Value,
-    pub assets: Vec<Value>,
-    pub goal_entries: Vec<Value>,
-    pub goal_success_weeks: Vec<Value>,
+    pub assets: Vec<Asset>,
+    pub goal_entries: Unconfirmed<GoalEntry>,
+    pub goal_success_weeks: Unconfirmed<GoalSuccessWeek>,
     pub reminders: Vec<Reminder>,
     pub writing_templates: Vec<WritingTemplate>,
     pub mood_icons_default_free_pack_id: i64,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 impl Default for Daylio {
@@ -44,64 +50,75 @@ impl Default for Daylio {
             .collect();
 
         Self {
-            version: 15,
+            // imports have no version, and must not upgrade the backups they are merged with
+            version: OLDEST_VERSION,
             is_reminder_on: Default::default(),
             custom_moods: moods,
             tags: vec![],
             day_entries: vec![],
             achievements: vec![],
             days_in_row_longest_chain: 0,
-            goals: vec![],
+            goals: Unconfirmed::default(),
             prefs: vec![
                 Pref {
                     key: "BACKUP_REMINDER_DONT_SHOW_AGAIN".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 0.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "LAST_DAYS_IN_ROWS_NUMBER".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 0.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "DAYS_IN_ROW_LONGEST_CHAIN".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 0.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "LAST_ENTRY_CREATION_TIME".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 0.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "COLOR_PALETTE_DEFAULT_CODE".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 1.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "PREDEFINED_MOODS_VARIANT".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 1.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "ONBOARDING_USER_PROPERTY".to_owned(),
                     pref_name: "default".to_owned(),
                     value: "finished".into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "WAS_EMOJI_SCREEN_VISITED".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 0.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "PIN_LOCK_STATE".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 2.into(),
+                    ..Default::default()
                 },
                 Pref {
                     key: "ARE_MEMORIES_VISIBLE_TO_USER".to_owned(),
                     pref_name: "default".to_owned(),
                     value: 1.into(),
+                    ..Default::default()
                 },
             ],
             tag_groups: vec![TagGroup {
@@ -109,6 +126,7 @@ impl Default for Daylio {
                 name: "Default".to_owned(),
                 is_expanded: true,
                 order: 1,
+                ..Default::default()
             }],
             metadata: Metadata::default(),
             mood_icons_pack_id: 1,
@@ -122,11 +140,12 @@ impl Default for Daylio {
                 }
             ),
             assets: vec![],
-            goal_entries: vec![],
-            goal_success_weeks: vec![],
+            goal_entries: Unconfirmed::default(),
+            goal_success_weeks: Unconfirmed::default(),
             reminders: vec![],
             writing_templates: vec![],
             mood_icons_default_free_pack_id: 1,
+            other: serde_json::Map::new(),
         }
     }
 }
@@ -147,6 +166,22 @@ pub struct CustomMood {
     pub predefined_name_id: i64,
     pub state: i64,
     pub created_at: i64,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+impl CustomMood {
+    /// The custom name, or the English name of the predefined mood, as written in CSV files
+    #[must_use]
+    pub fn name(&self) -> String {
+        if !self.custom_name.is_empty() {
+            return self.custom_name.clone();
+        }
+        crate::daylio_csv::predefined_mood_name(self.predefined_name_id).map_or_else(
+            || format!("predefined mood {}", self.predefined_name_id),
+            ToOwned::to_owned,
+        )
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -160,6 +195,8 @@ pub struct Tag {
     pub state: i64,
     #[serde(rename = "id_tag_group")]
     pub id_tag_group: i64,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -178,7 +215,123 @@ pub struct DayEntry {
     #[serde(rename = "note_title")]
     pub note_title: String,
     pub tags: Vec<i64>,
-    pub assets: Vec<Value>,
+    pub assets: Vec<i64>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+/// A photo or audio file attached to entries.
+/// The file itself lives in the backup archive, and is named after its checksum
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Asset {
+    pub id: i64,
+    pub checksum: String,
+    #[serde(rename = "type")]
+    pub asset_type: i64,
+    pub created_at: i64,
+    #[serde(default)]
+    pub created_at_offset: i64,
+    /// Fields we do not need to understand, such as the image metadata
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+/// Items whose field names come from no real backup. They are read as `T` when every item has the expected
+/// fields, and kept as they are otherwise, so that another layout does not stop the loading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Unconfirmed<T> {
+    Typed(Vec<T>),
+    Raw(Vec<Value>),
+}
+
+impl<T> Default for Unconfirmed<T> {
+    fn default() -> Self {
+        Unconfirmed::Typed(vec![])
+    }
+}
+
+impl<T> From<Vec<T>> for Unconfirmed<T> {
+    fn from(items: Vec<T>) -> Self {
+        Unconfirmed::Typed(items)
+    }
+}
+
+impl<T> Unconfirmed<T> {
+    #[must_use]
+    pub fn typed(&self) -> Option<&[T]> {
+        match self {
+            Unconfirmed::Typed(items) => Some(items),
+            Unconfirmed::Raw(_) => None,
+        }
+    }
+
+    pub fn typed_mut(&mut self) -> Option<&mut Vec<T>> {
+        match self {
+            Unconfirmed::Typed(items) => Some(items),
+            Unconfirmed::Raw(_) => None,
+        }
+    }
+}
+
+/// The fields that are not needed to link goals to their check-ins are optional
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Goal {
+    pub id: i64,
+    /// Id the check-ins and success weeks refer to, when the file has one beside `id`
+    #[serde(rename = "goal_id", skip_serializing_if = "Option::is_none")]
+    pub goal_id: Option<i64>,
+    pub name: String,
+    #[serde(rename = "id_icon", skip_serializing_if = "Option::is_none")]
+    pub id_icon: Option<i64>,
+    /// Goals can be tied to a tag, in which case entries with this tag count as check-ins
+    #[serde(rename = "id_tag", skip_serializing_if = "Option::is_none")]
+    pub id_tag: Option<i64>,
+    #[serde(rename = "created_at", skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    /// Fields we do not need to understand, such as the repeat settings and reminders
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+impl Goal {
+    /// Id of the goal in its check-ins and success weeks
+    #[must_use]
+    pub fn link_id(&self) -> i64 {
+        self.goal_id.unwrap_or(self.id)
+    }
+}
+
+/// A check-in for a goal, `goal_id` is the [`Goal::link_id`] of its goal
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalEntry {
+    pub id: i64,
+    pub goal_id: i64,
+    pub minute: i64,
+    pub hour: i64,
+    pub day: i64,
+    pub month: i64,
+    pub year: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+/// A week in which a goal has been reached
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalSuccessWeek {
+    pub goal_id: i64,
+    pub year: i64,
+    pub week: i64,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -714,6 +867,9 @@ pub struct Achievement {
         skip_serializing_if = "Option::is_none"
     )]
     pub ac_yearly_report_2016_unlocked_at: Option<i64>,
+    /// Achievements added by newer versions of the app
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -723,6 +879,8 @@ pub struct Pref {
     #[serde(rename = "pref_name")]
     pub pref_name: String,
     pub value: Value,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -733,6 +891,8 @@ pub struct TagGroup {
     #[serde(rename = "is_expanded")]
     pub is_expanded: bool,
     pub order: i64,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -751,6 +911,8 @@ pub struct Metadata {
     pub number_of_photos: i64,
     #[serde(rename = "photos_size")]
     pub photos_size: i64,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 impl Default for Metadata {
@@ -760,9 +922,10 @@ impl Default for Metadata {
             created_at: 0,
             is_auto_backup: false,
             platform: "android".to_owned(),
-            android_version: 15,
+            android_version: OLDEST_VERSION,
             number_of_photos: 0,
             photos_size: 0,
+            other: serde_json::Map::new(),
         }
     }
 }
@@ -776,6 +939,8 @@ pub struct Reminder {
     pub state: i64,
     #[serde(rename = "custom_text_enabled")]
     pub custom_text_enabled: bool,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -787,4 +952,6 @@ pub struct WritingTemplate {
     pub predefined_template_id: i64,
     pub title: String,
     pub body: String,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
 }
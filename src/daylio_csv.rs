@@ -0,0 +1,494 @@
+//! Reads and writes the CSV export of the Daylio app.
+//! Columns are `full_date, date, weekday, time, mood, activities, note_title, note`.
+
+use std::io::{Read, Write};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use color_eyre::eyre::WrapErr;
+use color_eyre::Result;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::locale::ENGLISH;
+use crate::{daylio, Daylio, merge, NUMBER_OF_PREDEFINED_MOODS};
+
+/// Separator of the names in the `activities` column
+pub(crate) const ACTIVITIES_SEPARATOR: &str = " | ";
+
+/// A row of the CSV export, as written by the app
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct CsvEntry {
+    /// `2023-01-24`
+    pub(crate) full_date: String,
+    /// `January 24`, localized
+    pub(crate) date: String,
+    /// `Tuesday`, localized
+    pub(crate) weekday: String,
+    /// `9:59 am` or `21:59`, depending on the phone settings
+    pub(crate) time: String,
+    pub(crate) mood: String,
+    pub(crate) activities: String,
+    /// Older exports do not have this column
+    #[serde(default)]
+    pub(crate) note_title: String,
+    pub(crate) note: String,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    let time = time.trim().to_lowercase().replace('.', "");
+
+    let parsed = if time.ends_with("am") || time.ends_with("pm") {
+        NaiveTime::parse_from_str(&time, "%I:%M %p")
+    } else {
+        NaiveTime::parse_from_str(&time, "%H:%M")
+    };
+    parsed.wrap_err_with(|| format!("Invalid time: {time}"))
+}
+
+fn parse_date(entry: &CsvEntry) -> Result<NaiveDateTime> {
+    let date = NaiveDate::parse_from_str(entry.full_date.trim(), "%Y-%m-%d")
+        .wrap_err_with(|| format!("Invalid date: {}", entry.full_date))?;
+    Ok(NaiveDateTime::new(date, parse_time(&entry.time)?))
+}
+
+fn split_activities(activities: &str) -> impl Iterator<Item = &str> {
+    activities
+        .split(ACTIVITIES_SEPARATOR.trim())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// An entry referring to its mood and tags by name, as in CSV and Markdown files
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct NamedEntry {
+    /// Local time of the entry
+    pub(crate) date: NaiveDateTime,
+    /// Milliseconds, as in `DayEntry`
+    pub(crate) time_zone_offset: i64,
+    pub(crate) mood: String,
+    pub(crate) tags: Vec<String>,
+    pub(crate) note_title: String,
+    pub(crate) note: String,
+}
+
+impl TryFrom<CsvEntry> for NamedEntry {
+    type Error = color_eyre::Report;
+
+    fn try_from(entry: CsvEntry) -> Result<Self> {
+        Ok(NamedEntry {
+            date: parse_date(&entry)?,
+            time_zone_offset: 0,
+            mood: entry.mood.trim().to_owned(),
+            tags: split_activities(&entry.activities)
+                .map(ToOwned::to_owned)
+                .collect(),
+            note_title: entry.note_title,
+            note: entry.note,
+        })
+    }
+}
+
+/// Id of the predefined mood. Files are written with the English names, other words are custom moods
+fn predefined_mood_idx(name: &str) -> Option<i64> {
+    ENGLISH.predefined_mood_idx(name)
+}
+
+fn list_moods(entries: &[NamedEntry]) -> Vec<daylio::CustomMood> {
+    let mut moods: Vec<daylio::CustomMood> = Vec::new();
+    let mut custom_count = 0;
+
+    for entry in entries {
+        let name = entry.mood.trim();
+        if let Some(idx) = predefined_mood_idx(name) {
+            if !moods.iter().any(|mood| mood.predefined_name_id == idx) {
+                moods.push(daylio::CustomMood {
+                    id: idx,
+                    predefined_name_id: idx,
+                    mood_group_id: idx,
+                    icon_id: idx,
+                    ..Default::default()
+                });
+            }
+        } else if !moods.iter().any(|mood| mood.custom_name == name) {
+            custom_count += 1;
+            moods.push(daylio::CustomMood {
+                id: NUMBER_OF_PREDEFINED_MOODS + custom_count,
+                custom_name: name.to_owned(),
+                predefined_name_id: -1,
+                // the name does not tell which group a custom mood belongs to, "meh" is the least wrong
+                mood_group_id: 3,
+                mood_group_order: 1,
+                icon_id: 1,
+                ..Default::default()
+            });
+        }
+    }
+
+    moods
+}
+
+fn list_tags(entries: &[NamedEntry]) -> Vec<daylio::Tag> {
+    let mut tags: Vec<daylio::Tag> = Vec::new();
+
+    for entry in entries {
+        for name in &entry.tags {
+            if !tags.iter().any(|tag| tag.name == *name) {
+                tags.push(daylio::Tag {
+                    id: tags.len() as i64 + 1,
+                    name: name.to_owned(),
+                    icon: 1,
+                    order: tags.len() as i64 + 1,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    tags
+}
+
+fn to_day_entry(
+    entry: NamedEntry,
+    moods: &[daylio::CustomMood],
+    tags: &[daylio::Tag],
+) -> daylio::DayEntry {
+    let date = entry.date;
+
+    let name = entry.mood.trim();
+    let mood = moods
+        .iter()
+        .find(|mood| match predefined_mood_idx(name) {
+            Some(idx) => mood.predefined_name_id == idx,
+            None => mood.custom_name == name,
+        })
+        .map_or(0, |mood| mood.id);
+
+    let entry_tags = entry
+        .tags
+        .iter()
+        .filter_map(|name| tags.iter().find(|tag| tag.name == *name))
+        .map(|tag| tag.id)
+        .collect();
+
+    daylio::DayEntry {
+        minute: i64::from(date.minute()),
+        hour: i64::from(date.hour()),
+        day: i64::from(date.day()),
+        month: i64::from(date.month()) - 1, // month is 0-indexed in Daylio
+        year: i64::from(date.year()),
+        datetime: date.and_utc().timestamp_millis() - entry.time_zone_offset,
+        time_zone_offset: entry.time_zone_offset,
+        mood,
+        note: entry.note,
+        note_title: entry.note_title,
+        tags: entry_tags,
+        ..Default::default()
+    }
+}
+
+/// English names of the predefined moods, as written by the app
+pub(crate) fn predefined_mood_name(predefined_name_id: i64) -> Option<&'static str> {
+    match predefined_name_id {
+        1 => Some("rad"),
+        2 => Some("good"),
+        3 => Some("meh"),
+        4 => Some("bad"),
+        5 => Some("awful"),
+        _ => None,
+    }
+}
+
+pub(crate) fn mood_name(daylio: &Daylio, id: i64) -> String {
+    daylio
+        .custom_moods
+        .iter()
+        .find(|mood| mood.id == id)
+        .map_or_else(String::new, daylio::CustomMood::name)
+}
+
+fn to_csv_entry(daylio: &Daylio, entry: &daylio::DayEntry) -> CsvEntry {
+    let date = NaiveDate::from_ymd_opt(
+        i32::try_from(entry.year).unwrap_or_default(),
+        u32::try_from(entry.month + 1).unwrap_or_default(), // month is 0-indexed in Daylio
+        u32::try_from(entry.day).unwrap_or_default(),
+    );
+    let time = NaiveTime::from_hms_opt(
+        u32::try_from(entry.hour).unwrap_or_default(),
+        u32::try_from(entry.minute).unwrap_or_default(),
+        0,
+    );
+
+    let activities = entry
+        .tags
+        .iter()
+        .filter_map(|id| daylio.tags.iter().find(|tag| tag.id == *id))
+        .map(|tag| tag.name.as_str())
+        .collect::<Vec<_>>()
+        .join(ACTIVITIES_SEPARATOR);
+
+    CsvEntry {
+        full_date: date
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        date: date
+            .map(|date| date.format("%B %-d").to_string())
+            .unwrap_or_default(),
+        weekday: date
+            .map(|date| date.format("%A").to_string())
+            .unwrap_or_default(),
+        time: time
+            .map(|time| time.format("%-I:%M %P").to_string())
+            .unwrap_or_default(),
+        mood: mood_name(daylio, entry.mood),
+        activities,
+        note_title: entry.note_title.clone(),
+        note: entry.note.clone(),
+    }
+}
+
+/// Writes the entries, newest first like the app
+pub(crate) fn write_csv(daylio: &Daylio, writer: impl Write) -> Result<()> {
+    let mut entries = daylio.day_entries.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|entry| -entry.datetime);
+
+    let mut writer = csv::Writer::from_writer(writer);
+    for entry in entries {
+        writer.serialize(to_csv_entry(daylio, entry))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub(crate) fn parse_csv(reader: impl Read) -> Result<Vec<CsvEntry>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::Headers)
+        .from_reader(reader)
+        .deserialize()
+        .enumerate()
+        // the first line is the header
+        .map(|(i, row)| row.wrap_err_with(|| format!("Invalid CSV row on line {}", i + 2)))
+        .collect()
+}
+
+impl From<Vec<NamedEntry>> for Daylio {
+    fn from(entries: Vec<NamedEntry>) -> Self {
+        let moods = list_moods(&entries);
+        let tags = list_tags(&entries);
+
+        let day_entries = entries
+            .into_iter()
+            .map(|entry| to_day_entry(entry, &moods, &tags))
+            .collect();
+
+        merge(
+            Daylio::default(),
+            Daylio {
+                custom_moods: moods,
+                tags,
+                day_entries,
+                ..Default::default()
+            },
+        )
+    }
+}
+
+impl TryFrom<Vec<CsvEntry>> for Daylio {
+    type Error = color_eyre::Report;
+
+    fn try_from(entries: Vec<CsvEntry>) -> Result<Self> {
+        let entries = entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let full_date = entry.full_date.clone();
+                NamedEntry::try_from(entry)
+                    .wrap_err_with(|| format!("Invalid entry {} ({full_date})", i + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(entries.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Timelike};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        let time = parse_time("9:59 am").unwrap();
+        assert_eq!((time.hour(), time.minute()), (9, 59));
+
+        let time = parse_time("12:05 AM").unwrap();
+        assert_eq!((time.hour(), time.minute()), (0, 5));
+
+        let time = parse_time("10:20 p.m.").unwrap();
+        assert_eq!((time.hour(), time.minute()), (22, 20));
+
+        let time = parse_time("21:07").unwrap();
+        assert_eq!((time.hour(), time.minute()), (21, 7));
+
+        assert!(parse_time("noon").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let csv = "full_date,date,weekday,time,mood,activities,note_title,note\n\
+            2023-01-24,January 24,Tuesday,9:59 am,rad,\"famille | sport\",Title,\"Multi\nline\"\n\
+            2023-01-11,January 11,Wednesday,10:20 pm,love it,\"\",,\"\"\n";
+
+        let entries = parse_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            parse_date(&entries[0]).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 1, 24)
+                .unwrap()
+                .and_hms_opt(9, 59, 0)
+                .unwrap()
+        );
+        assert_eq!(entries[0].note, "Multi\nline");
+        assert_eq!(
+            split_activities(&entries[0].activities).collect::<Vec<_>>(),
+            vec!["famille", "sport"]
+        );
+        assert_eq!(entries[1].mood, "love it");
+    }
+
+    #[test]
+    fn test_old_csv_without_note_title() {
+        let csv = "full_date,date,weekday,time,mood,activities,note\n\
+            2015-05-16,May 16,Saturday,8:00 pm,meh,,Old note\n";
+
+        let entries = parse_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(entries[0].note_title, "");
+        assert_eq!(entries[0].note, "Old note");
+    }
+
+    #[test]
+    fn test_daylio_from_csv_entries() {
+        let entries = vec![
+            CsvEntry {
+                full_date: "2023-01-24".to_owned(),
+                time: "9:59 am".to_owned(),
+                mood: "rad".to_owned(),
+                activities: "sport | famille".to_owned(),
+                ..Default::default()
+            },
+            CsvEntry {
+                full_date: "2023-01-11".to_owned(),
+                time: "22:20".to_owned(),
+                mood: "love it".to_owned(),
+                activities: "famille".to_owned(),
+                note: "A note".to_owned(),
+                ..Default::default()
+            },
+        ];
+
+        let daylio = Daylio::try_from(entries).unwrap();
+
+        assert_eq!(
+            daylio.custom_moods.len(),
+            usize::try_from(NUMBER_OF_PREDEFINED_MOODS).unwrap() + 1
+        );
+        assert_eq!(daylio.tags.len(), 2);
+        assert_eq!(daylio.day_entries.len(), 2);
+
+        let love_it = daylio
+            .custom_moods
+            .iter()
+            .find(|mood| mood.custom_name == "love it")
+            .unwrap();
+        let entry = daylio
+            .day_entries
+            .iter()
+            .find(|entry| entry.note == "A note")
+            .unwrap();
+        assert_eq!(entry.mood, love_it.id);
+        assert_eq!((entry.hour, entry.minute), (22, 20));
+        assert_eq!(entry.month, 0);
+    }
+
+    #[test]
+    fn test_predefined_names_of_other_languages_are_custom_moods() {
+        let entries = ["super", "normal", "Good"].map(|mood| CsvEntry {
+            full_date: "2023-01-24".to_owned(),
+            time: "9:59 am".to_owned(),
+            mood: mood.to_owned(),
+            ..Default::default()
+        });
+
+        let daylio = Daylio::try_from(entries.to_vec()).unwrap();
+        let moods = daylio
+            .day_entries
+            .iter()
+            .map(|entry| {
+                let mood = daylio
+                    .custom_moods
+                    .iter()
+                    .find(|mood| mood.id == entry.mood);
+                let mood = mood.unwrap();
+                (mood.custom_name.as_str(), mood.predefined_name_id)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(moods, [("super", -1), ("normal", -1), ("", 2)]);
+    }
+
+    #[test]
+    fn test_csv_entry_from_day_entry() {
+        let daylio = Daylio {
+            tags: vec![
+                daylio::Tag {
+                    id: 1,
+                    name: "sport".to_owned(),
+                    ..Default::default()
+                },
+                daylio::Tag {
+                    id: 2,
+                    name: "famille".to_owned(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let entry = daylio::DayEntry {
+            year: 2023,
+            month: 0,
+            day: 24,
+            hour: 21,
+            minute: 5,
+            mood: 4,
+            tags: vec![2, 1],
+            note: "A note".to_owned(),
+            ..Default::default()
+        };
+
+        let expected = CsvEntry {
+            full_date: "2023-01-24".to_owned(),
+            date: "January 24".to_owned(),
+            weekday: "Tuesday".to_owned(),
+            time: "9:05 pm".to_owned(),
+            mood: "bad".to_owned(),
+            activities: "famille | sport".to_owned(),
+            note_title: String::new(),
+            note: "A note".to_owned(),
+        };
+        assert_eq!(to_csv_entry(&daylio, &entry), expected);
+    }
+
+    #[test]
+    fn test_invalid_row_is_reported() {
+        let entries = vec![CsvEntry {
+            full_date: "24/01/2023".to_owned(),
+            time: "9:59 am".to_owned(),
+            mood: "rad".to_owned(),
+            ..Default::default()
+        }];
+
+        let error = Daylio::try_from(entries).unwrap_err();
+        assert!(format!("{error:?}").contains("24/01/2023"));
+    }
+}
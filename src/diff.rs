@@ -0,0 +1,550 @@
+//! Differences between two backups, by meaning rather than by JSON text.
+//! Ids are ignored: moods and tags are matched as the merge does, entries by their timestamp
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+use crate::merge::ProjectEq;
+use crate::validate::fields_time;
+use crate::{CustomMood, DayEntry, Daylio, Tag};
+
+/// A mood or a tag that is only in one of the backups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameChange {
+    Added(String),
+    Removed(String),
+    /// Created at the same time as no other, in the same group, but with another name
+    Renamed {
+        from: String,
+        to: String,
+    },
+}
+
+/// A field of an entry that differs, with names instead of ids
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+/// Entries are given by their timestamp, and the local time of their fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryChange {
+    Added {
+        datetime: i64,
+        time: String,
+    },
+    Removed {
+        datetime: i64,
+        time: String,
+    },
+    Modified {
+        datetime: i64,
+        time: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// A setting that has been added, removed or changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefChange {
+    pub key: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// What changes from the first backup to the second one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupDiff {
+    pub moods: Vec<NameChange>,
+    pub tags: Vec<NameChange>,
+    /// Oldest first
+    pub entries: Vec<EntryChange>,
+    pub prefs: Vec<PrefChange>,
+}
+
+impl BackupDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.moods.is_empty()
+            && self.tags.is_empty()
+            && self.entries.is_empty()
+            && self.prefs.is_empty()
+    }
+}
+
+/// Items of one list that have no equal in the other one, both ways, then paired by creation time.
+/// A pair is a rename only when no other item of either list was created at the same time, and both are of the same
+/// kind
+fn name_changes<T, P, K>(
+    first: &[T],
+    second: &[T],
+    name: impl Fn(&T) -> String,
+    created_at: impl Fn(&T) -> i64,
+    kind: impl Fn(&T) -> K,
+) -> Vec<NameChange>
+where
+    T: ProjectEq<P>,
+    P: PartialEq,
+    K: PartialEq,
+{
+    let missing = |items: &[T], others: &[T]| {
+        items
+            .iter()
+            .filter(|item| !others.iter().any(|other| other.project() == item.project()))
+            .map(|item| (name(item), created_at(item), kind(item)))
+            .collect::<Vec<_>>()
+    };
+    let removed = missing(first, second);
+    let mut added = missing(second, first);
+    let created_count = |items: &[(String, i64, K)], created| {
+        items
+            .iter()
+            .filter(|(_, other, _)| *other == created)
+            .count()
+    };
+
+    let mut changes = Vec::new();
+    for (from, created, from_kind) in &removed {
+        // items created by an import have no creation time
+        let unique = *created != 0
+            && created_count(&removed, *created) == 1
+            && created_count(&added, *created) == 1;
+        let renamed = added
+            .iter()
+            .position(|(_, other, kind)| unique && other == created && kind == from_kind);
+        match renamed {
+            Some(i) => {
+                let (to, _, _) = added.remove(i);
+                changes.push(NameChange::Renamed {
+                    from: from.clone(),
+                    to,
+                });
+            }
+            None => changes.push(NameChange::Removed(from.clone())),
+        }
+    }
+    changes.extend(
+        added
+            .into_iter()
+            .map(|(name, _, _)| NameChange::Added(name)),
+    );
+    changes
+}
+
+fn entry_mood(daylio: &Daylio, entry: &DayEntry) -> String {
+    daylio
+        .custom_moods
+        .iter()
+        .find(|mood| mood.id == entry.mood)
+        .map_or_else(|| format!("mood {}", entry.mood), CustomMood::name)
+}
+
+fn entry_tags(daylio: &Daylio, entry: &DayEntry) -> String {
+    let mut names = entry
+        .tags
+        .iter()
+        .map(|id| {
+            daylio
+                .tags
+                .iter()
+                .find(|tag| tag.id == *id)
+                .map_or_else(|| format!("tag {id}"), |tag| tag.name.clone())
+        })
+        .collect::<Vec<_>>();
+    names.sort_by_key(|name| name.to_lowercase());
+    names.join(", ")
+}
+
+fn field_changes(
+    first: &Daylio,
+    first_entry: &DayEntry,
+    second: &Daylio,
+    second_entry: &DayEntry,
+) -> Vec<FieldChange> {
+    // names are compared as the merge matches them, ignoring the case
+    let fields = [
+        (
+            "mood",
+            entry_mood(first, first_entry),
+            entry_mood(second, second_entry),
+            true,
+        ),
+        (
+            "note_title",
+            first_entry.note_title.clone(),
+            second_entry.note_title.clone(),
+            false,
+        ),
+        (
+            "note",
+            first_entry.note.clone(),
+            second_entry.note.clone(),
+            false,
+        ),
+        (
+            "tags",
+            entry_tags(first, first_entry),
+            entry_tags(second, second_entry),
+            true,
+        ),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, before, after, ignore_case)| {
+            if *ignore_case {
+                before.to_lowercase() != after.to_lowercase()
+            } else {
+                before != after
+            }
+        })
+        .map(|(field, before, after, _)| FieldChange {
+            field,
+            before,
+            after,
+        })
+        .collect()
+}
+
+fn entry_changes(first: &Daylio, second: &Daylio) -> Vec<EntryChange> {
+    let mut by_datetime: BTreeMap<i64, (Vec<&DayEntry>, Vec<&DayEntry>)> = BTreeMap::new();
+    for entry in &first.day_entries {
+        by_datetime.entry(entry.datetime).or_default().0.push(entry);
+    }
+    for entry in &second.day_entries {
+        by_datetime.entry(entry.datetime).or_default().1.push(entry);
+    }
+
+    let mut changes = Vec::new();
+    for (datetime, (before, after)) in by_datetime {
+        // entries recorded at the same time are paired in order
+        for (first_entry, second_entry) in before.iter().zip(&after) {
+            let fields = field_changes(first, first_entry, second, second_entry);
+            if !fields.is_empty() {
+                changes.push(EntryChange::Modified {
+                    datetime,
+                    time: fields_time(second_entry),
+                    fields,
+                });
+            }
+        }
+        for entry in before.iter().skip(after.len()) {
+            changes.push(EntryChange::Removed {
+                datetime,
+                time: fields_time(entry),
+            });
+        }
+        for entry in after.iter().skip(before.len()) {
+            changes.push(EntryChange::Added {
+                datetime,
+                time: fields_time(entry),
+            });
+        }
+    }
+    changes
+}
+
+fn pref_changes(first: &Daylio, second: &Daylio) -> Vec<PrefChange> {
+    // values before and after
+    type Values<'a> = (Option<&'a Value>, Option<&'a Value>);
+    let mut by_key: BTreeMap<(&str, &str), Values> = BTreeMap::new();
+    for pref in &first.prefs {
+        by_key
+            .entry((pref.pref_name.as_str(), pref.key.as_str()))
+            .or_default()
+            .0 = Some(&pref.value);
+    }
+    for pref in &second.prefs {
+        by_key
+            .entry((pref.pref_name.as_str(), pref.key.as_str()))
+            .or_default()
+            .1 = Some(&pref.value);
+    }
+
+    by_key
+        .into_iter()
+        .filter(|(_, (before, after))| before != after)
+        .map(|((_, key), (before, after))| PrefChange {
+            key: key.to_owned(),
+            before: before.cloned(),
+            after: after.cloned(),
+        })
+        .collect()
+}
+
+/// Compares two backups, for example a backup and the result of a merge before restoring it
+#[must_use]
+pub fn diff(first: &Daylio, second: &Daylio) -> BackupDiff {
+    BackupDiff {
+        moods: name_changes(
+            &first.custom_moods,
+            &second.custom_moods,
+            CustomMood::name,
+            |mood: &CustomMood| mood.created_at,
+            |mood| (mood.mood_group_id, mood.predefined_name_id == -1),
+        ),
+        tags: name_changes(
+            &first.tags,
+            &second.tags,
+            |tag: &Tag| tag.name.clone(),
+            |tag| tag.created_at,
+            // group ids change from one backup to the other
+            |tag| tag.id_tag_group == 0,
+        ),
+        entries: entry_changes(first, second),
+        prefs: pref_changes(first, second),
+    }
+}
+
+fn write_names(f: &mut Formatter<'_>, title: &str, changes: &[NameChange]) -> std::fmt::Result {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{title}:")?;
+    for change in changes {
+        match change {
+            NameChange::Added(name) => writeln!(f, "  + {name}")?,
+            NameChange::Removed(name) => writeln!(f, "  - {name}")?,
+            NameChange::Renamed { from, to } => writeln!(f, "  ~ {from} -> {to}")?,
+        }
+    }
+    Ok(())
+}
+
+impl Display for BackupDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_names(f, "Moods", &self.moods)?;
+        write_names(f, "Tags", &self.tags)?;
+
+        if !self.entries.is_empty() {
+            writeln!(f, "Entries:")?;
+        }
+        for change in &self.entries {
+            match change {
+                EntryChange::Added { time, .. } => writeln!(f, "  + {time}")?,
+                EntryChange::Removed { time, .. } => writeln!(f, "  - {time}")?,
+                EntryChange::Modified { time, fields, .. } => {
+                    writeln!(f, "  ~ {time}")?;
+                    for field in fields {
+                        writeln!(
+                            f,
+                            "      {}: {:?} -> {:?}",
+                            field.field, field.before, field.after
+                        )?;
+                    }
+                }
+            }
+        }
+
+        if !self.prefs.is_empty() {
+            writeln!(f, "Settings:")?;
+        }
+        let show = |value: &Option<Value>| value.as_ref().map_or("-".to_owned(), Value::to_string);
+        for pref in &self.prefs {
+            writeln!(
+                f,
+                "  {}: {} -> {}",
+                pref.key,
+                show(&pref.before),
+                show(&pref.after)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Pref;
+
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let tag = |id, name: &str, created_at| Tag {
+            id,
+            name: name.to_owned(),
+            created_at,
+            ..Default::default()
+        };
+        let entry = |datetime, tags: Vec<i64>, note: &str| DayEntry {
+            datetime,
+            mood: 1,
+            tags,
+            note: note.to_owned(),
+            ..Default::default()
+        };
+
+        let first = Daylio {
+            tags: vec![tag(1, "work", 10), tag(2, "gym", 20), tag(3, "read", 30)],
+            day_entries: vec![
+                entry(1000, vec![1, 2], "note"),
+                entry(2000, vec![], ""),
+                entry(3000, vec![3], ""),
+            ],
+            ..Default::default()
+        };
+        let mut second = Daylio {
+            // other ids, "gym" renamed, "read" removed, "cook" added
+            tags: vec![tag(5, "Work", 10), tag(6, "sport", 20), tag(7, "cook", 40)],
+            day_entries: vec![
+                entry(1000, vec![6, 5], "note"),
+                entry(2000, vec![7], "edited"),
+                entry(4000, vec![], ""),
+            ],
+            ..Default::default()
+        };
+        second.day_entries[1].mood = 5;
+        second.prefs[0].value = 1.into();
+        second.prefs.push(Pref {
+            key: "NEW_SETTING".to_owned(),
+            pref_name: "default".to_owned(),
+            value: true.into(),
+            ..Default::default()
+        });
+
+        let diff = diff(&first, &second);
+        assert_eq!(
+            diff.tags,
+            vec![
+                NameChange::Renamed {
+                    from: "gym".to_owned(),
+                    to: "sport".to_owned()
+                },
+                NameChange::Removed("read".to_owned()),
+                NameChange::Added("cook".to_owned()),
+            ]
+        );
+        assert_eq!(diff.moods, vec![]);
+
+        let time = fields_time(&DayEntry::default());
+        let change = |field, before: &str, after: &str| FieldChange {
+            field,
+            before: before.to_owned(),
+            after: after.to_owned(),
+        };
+        assert_eq!(
+            diff.entries,
+            vec![
+                // only the name of the tag changed, "work" is the same tag
+                EntryChange::Modified {
+                    datetime: 1000,
+                    time: time.clone(),
+                    fields: vec![change("tags", "gym, work", "sport, Work")],
+                },
+                EntryChange::Modified {
+                    datetime: 2000,
+                    time: time.clone(),
+                    fields: vec![
+                        change("mood", "rad", "awful"),
+                        change("note", "", "edited"),
+                        change("tags", "", "cook"),
+                    ],
+                },
+                EntryChange::Removed {
+                    datetime: 3000,
+                    time: time.clone(),
+                },
+                EntryChange::Added {
+                    datetime: 4000,
+                    time,
+                },
+            ]
+        );
+
+        assert_eq!(
+            diff.prefs,
+            vec![
+                PrefChange {
+                    key: "BACKUP_REMINDER_DONT_SHOW_AGAIN".to_owned(),
+                    before: Some(0.into()),
+                    after: Some(1.into()),
+                },
+                PrefChange {
+                    key: "NEW_SETTING".to_owned(),
+                    before: None,
+                    after: Some(true.into()),
+                },
+            ]
+        );
+
+        assert!(super::diff(&first, &first).is_empty());
+    }
+
+    #[test]
+    fn test_ambiguous_renames() {
+        // tags created together, by an import for example
+        let tag = |name: &str| Tag {
+            name: name.to_owned(),
+            created_at: 50,
+            ..Default::default()
+        };
+        let first = [tag("work"), tag("gym")];
+        let second = [tag("sport"), tag("cook")];
+        let changes = name_changes(
+            &first,
+            &second,
+            |tag| tag.name.clone(),
+            |tag| tag.created_at,
+            |_| (),
+        );
+        assert_eq!(
+            changes,
+            vec![
+                NameChange::Removed("work".to_owned()),
+                NameChange::Removed("gym".to_owned()),
+                NameChange::Added("sport".to_owned()),
+                NameChange::Added("cook".to_owned()),
+            ]
+        );
+
+        let mood = |name: &str, mood_group_id| CustomMood {
+            custom_name: name.to_owned(),
+            mood_group_id,
+            predefined_name_id: -1,
+            created_at: 50,
+            ..Default::default()
+        };
+        let diff = |first: CustomMood, second: CustomMood| {
+            let first = Daylio {
+                custom_moods: vec![first],
+                ..Default::default()
+            };
+            let second = Daylio {
+                custom_moods: vec![second],
+                ..Default::default()
+            };
+            super::diff(&first, &second).moods
+        };
+        assert_eq!(
+            diff(mood("tired", 4), mood("exhausted", 4)),
+            vec![NameChange::Renamed {
+                from: "tired".to_owned(),
+                to: "exhausted".to_owned()
+            }]
+        );
+        // another group, or a predefined mood, is another mood
+        assert_eq!(
+            diff(mood("tired", 4), mood("happy", 2)),
+            vec![
+                NameChange::Removed("tired".to_owned()),
+                NameChange::Added("happy".to_owned()),
+            ]
+        );
+        let predefined = CustomMood {
+            custom_name: String::new(),
+            predefined_name_id: 4,
+            ..mood("", 4)
+        };
+        assert_eq!(
+            diff(mood("tired", 4), predefined),
+            vec![
+                NameChange::Removed("tired".to_owned()),
+                NameChange::Added("bad".to_owned()),
+            ]
+        );
+    }
+}
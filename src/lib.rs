@@ -4,14 +4,43 @@
 #![allow(clippy::too_many_lines)]
 #![allow(clippy::cast_possible_wrap)]
 
+pub use analyze_pdf::{
+    PdfImport, PdfOptions, PdfVerification, StatDrift, StatKind, TagAmbiguity, TitleHeuristic,
+};
 pub use anonymize::anonymize;
+pub use backup::*;
+pub use chrono_tz::Tz;
+pub use conflict::{AskResolution, Conflict, MergeStrategy, Resolution};
 pub use daylio::*;
+pub use diff::{BackupDiff, diff, EntryChange, FieldChange, NameChange, PrefChange};
 pub use load_store::*;
-pub use merge::merge;
+pub use locale::{DateOrder, LOCALES, PdfLocale};
+pub use markdown::{export_markdown, ExportedFile, html_to_markdown, NoteLayout};
+pub use merge::{merge, merge_backups, merge_with_report, merge_with_strategy};
+pub use parse_pdf::PdfDiagnostic;
+pub use repair::{Fix, PLACEHOLDER_MOOD};
+pub use report::{IdMapping, MergeReport};
+pub use schema::{check_version, LATEST_VERSION, migrate, OLDEST_VERSION};
+pub use three_way::{merge_backups_three_way, merge_three_way};
+pub use time_zone::{apply_time_zone, infer_time_zone, match_seconds};
+pub use validate::{IdKind, Problem};
 
 mod analyze_pdf;
 mod anonymize;
+mod backup;
+mod conflict;
 mod daylio;
+mod daylio_csv;
+mod diff;
 mod load_store;
+mod locale;
+mod markdown;
 mod merge;
 mod parse_pdf;
+mod pdf_text;
+mod repair;
+mod report;
+mod schema;
+mod three_way;
+mod time_zone;
+mod validate;
@@ -1,16 +1,21 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
-use color_eyre::eyre::{ContextCompat, eyre, WrapErr};
+use chrono::DateTime;
+use color_eyre::eyre::{bail, ContextCompat, eyre, WrapErr};
 use color_eyre::Result;
 use zip::write::SimpleFileOptions;
 use zip::ZipWriter;
 
-use crate::analyze_pdf::ProcessedPdf;
-use crate::Daylio;
+use crate::schema::read_version;
+use crate::{
+    AssetFile, ASSETS_DIR, Backup, check_version, Daylio, export_markdown, NoteLayout, PdfImport,
+    PdfOptions,
+};
 
 pub fn load_daylio_backup(path: &Path) -> Result<Daylio> {
     let file = File::open(path)?;
@@ -23,60 +28,337 @@ pub fn load_daylio_backup(path: &Path) -> Result<Daylio> {
     data = data.replace('\n', "");
 
     let data = BASE64.decode(data)?;
+    check_version(read_version(&data)?)?;
 
     serde_json::from_slice(&data).wrap_err("Failed to parse Daylio backup")
 }
 
+/// Loads a `.daylio` archive, including the photos and audio files
+pub fn load_daylio_backup_with_assets(path: &Path) -> Result<Backup> {
+    let daylio = load_daylio_backup(path)?;
+
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut assets = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() || !file.name().starts_with(ASSETS_DIR) {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .wrap_err_with(|| format!("Failed to read asset {}", file.name()))?;
+        assets.push(AssetFile {
+            path: file.name().to_owned(),
+            data,
+        });
+    }
+
+    Ok(Backup { daylio, assets })
+}
+
 pub fn load_daylio_json(path: &Path) -> Result<Daylio> {
     let mut file = File::open(path)?;
     let mut data = String::new();
     file.read_to_string(&mut data)?;
+    check_version(read_version(data.as_bytes())?)?;
 
     serde_json::from_str(&data).wrap_err("Failed to parse Daylio JSON")
 }
 
+/// Reads a PDF export, detecting its language
 pub fn load_daylio_pdf(path: &Path) -> Result<Daylio> {
-    crate::parse_pdf::parse_pdf(path)
-        .map(Into::<ProcessedPdf>::into)
-        .map(Into::into)
+    load_daylio_pdf_with_options(path, &PdfOptions::default())
 }
 
-pub fn load_daylio(path: &Path) -> Result<Daylio> {
-    if let Some(ext) = path.extension() {
+pub fn load_daylio_pdf_with_options(path: &Path, options: &PdfOptions) -> Result<Daylio> {
+    import_pdf(path, options).map(|import| import.daylio)
+}
+
+/// Reads the text of a PDF export, as extracted by `pdftotext -layout`
+pub fn load_daylio_pdf_text(path: &Path, options: &PdfOptions) -> Result<Daylio> {
+    import_pdf_text(path, options).map(|import| import.daylio)
+}
+
+/// Reads a PDF export, with the entries that were skipped in lenient mode
+pub fn import_pdf(path: &Path, options: &PdfOptions) -> Result<PdfImport> {
+    let parsed = crate::parse_pdf::parse_pdf(path, options.locale, options.lenient)?;
+    PdfImport::new(&parsed, options)
+}
+
+/// Same as [`import_pdf`], from the text extracted by `pdftotext -layout`
+pub fn import_pdf_text(path: &Path, options: &PdfOptions) -> Result<PdfImport> {
+    let text = std::fs::read_to_string(path)?.replace("\r\n", "\n");
+    let parsed = crate::parse_pdf::parse_pdf_text(&text, options.locale, options.lenient)?;
+    PdfImport::new(&parsed, options)
+}
+
+/// Reads the CSV export of the app
+pub fn load_daylio_csv(path: &Path) -> Result<Daylio> {
+    let file = File::open(path)?;
+    crate::daylio_csv::parse_csv(file)?.try_into()
+}
+
+/// Reads a folder of Markdown daily notes, such as an Obsidian vault or a Logseq graph
+pub fn load_markdown(dir: &Path) -> Result<Daylio> {
+    fn read_notes(dir: &Path, notes: &mut Vec<(String, String)>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            if name.starts_with('.') {
+                continue; // .obsidian, .trash, ...
+            }
+
+            if path.is_dir() {
+                read_notes(&path, notes)?;
+            } else if let Some(stem) = name.strip_suffix(".md") {
+                let content = std::fs::read_to_string(&path)
+                    .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+                notes.push((stem.to_owned(), content));
+            }
+        }
+        Ok(())
+    }
+
+    let mut notes = Vec::new();
+    read_notes(dir, &mut notes)?;
+
+    let entries = crate::markdown::parse_markdown_notes(&notes);
+    if entries.is_empty() {
+        bail!("No daily notes found in {}", dir.display());
+    }
+    Ok(entries.into())
+}
+
+/// Supported file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Zip archive exported by the app
+    Daylio,
+    Json,
+    /// CSV export of the app
+    Csv,
+    /// PDF export, can only be read
+    Pdf,
+    /// Folder of Markdown notes
+    Markdown,
+}
+
+impl Format {
+    /// Guesses the format from the file extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(Self::Markdown);
+        }
+        let ext = path.extension().wrap_err("Missing file extension")?;
         let ext = ext.to_str().wrap_err("Unknown file extension")?;
         match ext.to_lowercase().as_ref() {
-            "daylio" => load_daylio_backup(path),
-            "json" => load_daylio_json(path),
-            "pdf" => load_daylio_pdf(path),
+            "daylio" => Ok(Self::Daylio),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "pdf" => Ok(Self::Pdf),
             _ => Err(eyre!("Unknown file extension")),
         }
-    } else {
-        Err(eyre!("Missing file extension"))
+    }
+}
+
+pub fn load_daylio(path: &Path) -> Result<Daylio> {
+    load_daylio_as(path, Format::from_path(path)?)
+}
+
+pub fn load_daylio_as(path: &Path, format: Format) -> Result<Daylio> {
+    match format {
+        Format::Daylio => load_daylio_backup(path),
+        Format::Json => load_daylio_json(path),
+        Format::Csv => load_daylio_csv(path),
+        Format::Pdf => load_daylio_pdf(path),
+        Format::Markdown => load_markdown(path),
+    }
+}
+
+/// Loads any supported file. Only `.daylio` archives can contain assets
+pub fn load_backup(path: &Path) -> Result<Backup> {
+    load_backup_as(path, Format::from_path(path)?)
+}
+
+pub fn load_backup_as(path: &Path, format: Format) -> Result<Backup> {
+    match format {
+        Format::Daylio => load_daylio_backup_with_assets(path),
+        _ => load_daylio_as(path, format).map(Backup::from),
     }
 }
 
 pub fn store_daylio_backup(daylio: &Daylio, path: &Path) -> Result<()> {
-    let file = File::create(path)?;
+    store_daylio_backup_with_assets(daylio, &[], path)
+}
 
-    let mut archive = ZipWriter::new(file);
-    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+/// Stores a `.daylio` archive, including the photos and audio files
+pub fn store_backup(backup: &Backup, path: &Path) -> Result<()> {
+    store_daylio_backup_with_assets(&backup.daylio, &backup.assets, path)
+}
 
-    let json = serde_json::to_string_pretty(daylio)?;
+fn store_daylio_backup_with_assets(
+    daylio: &Daylio,
+    assets: &[AssetFile],
+    path: &Path,
+) -> Result<()> {
+    write_atomically(path, |file| {
+        let mut archive = ZipWriter::new(file);
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
 
-    let data = BASE64.encode(json.as_bytes());
+        let json = serde_json::to_string_pretty(daylio)?;
 
-    archive.start_file("backup.daylio", options)?;
-    archive.write_all(data.as_bytes())?;
-    archive.finish()?;
+        let data = BASE64.encode(json.as_bytes());
 
-    Ok(())
+        archive.start_file("backup.daylio", options)?;
+        archive.write_all(data.as_bytes())?;
+
+        for asset in assets {
+            archive.start_file(asset.path.as_str(), options)?;
+            archive.write_all(&asset.data)?;
+        }
+
+        archive.finish()?;
+
+        Ok(())
+    })
+}
+
+/// Stores a backup in the given format. Assets are lost when writing JSON or CSV
+pub fn store_backup_as(backup: &Backup, path: &Path, format: Format) -> Result<()> {
+    match format {
+        Format::Daylio => store_backup(backup, path),
+        Format::Json => store_daylio_json(&backup.daylio, path),
+        Format::Csv => store_daylio_csv(&backup.daylio, path),
+        Format::Markdown => store_markdown(backup, path, NoteLayout::PerDay),
+        Format::Pdf => Err(eyre!("Writing PDF files is not supported")),
+    }
 }
 
 pub fn store_daylio_json(daylio: &Daylio, path: &Path) -> Result<()> {
     let json = serde_json::to_string_pretty(daylio)?;
 
-    let mut file = File::create(path)?;
-    file.write_all(json.as_bytes())?;
+    write_atomically(path, |file| {
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    })
+}
+
+/// Writes the same columns as the CSV export of the app
+pub fn store_daylio_csv(daylio: &Daylio, path: &Path) -> Result<()> {
+    write_atomically(path, |file| crate::daylio_csv::write_csv(daylio, file))
+}
+
+/// Writes Markdown notes and their assets to the `dir` folder
+pub fn store_markdown(backup: &Backup, dir: &Path, layout: NoteLayout) -> Result<()> {
+    for file in export_markdown(backup, layout) {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_atomically(&path, |out| {
+            out.write_all(&file.data)?;
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+/// Writes to a temporary file next to `path`, then renames it into place.
+/// A crash while writing leaves the previous file untouched
+fn write_atomically(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let file_name = path.file_name().wrap_err("Missing file name")?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&temp_path)?;
+        write(&mut file)?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result.wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+/// Resolves symbolic links and `..`, even if the file does not exist yet
+fn resolve(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            parent
+                .canonicalize()
+                .map_or_else(|_| path.to_owned(), |parent| parent.join(name))
+        }
+        _ => path.to_owned(),
+    }
+}
+
+/// Whether both paths are hard links to the same existing file
+#[cfg(unix)]
+fn same_inode(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
 
+#[cfg(not(unix))]
+fn same_inode(_: &Path, _: &Path) -> bool {
+    false
+}
+
+/// Fails if `output` is the same file as one of the `inputs`, including through hard links
+pub fn ensure_not_input(output: &Path, inputs: &[PathBuf]) -> Result<()> {
+    let output_resolved = resolve(output);
+    for input in inputs {
+        if resolve(input) == output_resolved || same_inode(input, output) {
+            bail!(
+                "Refusing to overwrite input file {} with the output",
+                input.display()
+            );
+        }
+    }
     Ok(())
 }
+
+/// Copies `path` to `<path>.<timestamp>.bak` if it exists, and returns the copy
+pub fn backup_existing(path: &Path) -> Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
+    let timestamp = DateTime::from_timestamp(seconds.try_into()?, 0)
+        .wrap_err("Invalid system time")?
+        .format("%Y%m%d-%H%M%S");
+
+    let mut bak_name = path.file_name().wrap_err("Missing file name")?.to_owned();
+    bak_name.push(format!(".{timestamp}.bak"));
+    let bak_path = path.with_file_name(bak_name);
+
+    std::fs::copy(path, &bak_path)
+        .wrap_err_with(|| format!("Failed to back up {}", path.display()))?;
+    Ok(Some(bak_path))
+}
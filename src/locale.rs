@@ -0,0 +1,415 @@
+//! Languages of the PDF export: month and weekday names, date order and predefined mood names.
+//! The clock is not part of the language, 12 or 24 hours depends on the phone settings.
+
+use chrono::NaiveDate;
+
+/// Order of the parts of a date, such as `August 2, 2022` or `2 août 2022`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    MonthDayYear,
+    DayMonthYear,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct PdfLocale {
+    /// ISO 639-1 code, such as `fr`
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Lowercase, from January. Some languages use another form in dates, such as `sierpnia` for `sierpień`
+    pub months: [&'static [&'static str]; 12],
+    /// Lowercase, from Monday
+    pub weekdays: [&'static str; 7],
+    pub date_order: DateOrder,
+    /// Words of a date that are not part of it, such as `de` in `2 de agosto de 2022`
+    pub date_fillers: &'static [&'static str],
+    /// Lowercase names of the predefined moods, from rad to awful
+    pub moods: [&'static str; 5],
+    /// Only checked against hand-written texts, see `tests/data/synthetic/README.md`.
+    /// Used when chosen by the user, never detected
+    pub experimental: bool,
+}
+
+pub const LOCALES: &[PdfLocale] = &[
+    PdfLocale {
+        code: "en",
+        name: "English",
+        months: [
+            &["january"],
+            &["february"],
+            &["march"],
+            &["april"],
+            &["may"],
+            &["june"],
+            &["july"],
+            &["august"],
+            &["september"],
+            &["october"],
+            &["november"],
+            &["december"],
+        ],
+        weekdays: [
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+        ],
+        date_order: DateOrder::MonthDayYear,
+        date_fillers: &[],
+        moods: ["rad", "good", "meh", "bad", "awful"],
+        experimental: false,
+    },
+    PdfLocale {
+        code: "fr",
+        name: "French",
+        months: [
+            &["janvier"],
+            &["février"],
+            &["mars"],
+            &["avril"],
+            &["mai"],
+            &["juin"],
+            &["juillet"],
+            &["août"],
+            &["septembre"],
+            &["octobre"],
+            &["novembre"],
+            &["décembre"],
+        ],
+        weekdays: [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &[],
+        moods: ["super", "bien", "mouais", "mauvais", "horrible"],
+        experimental: false,
+    },
+    PdfLocale {
+        code: "de",
+        name: "German",
+        months: [
+            &["januar"],
+            &["februar"],
+            &["märz"],
+            &["april"],
+            &["mai"],
+            &["juni"],
+            &["juli"],
+            &["august"],
+            &["september"],
+            &["oktober"],
+            &["november"],
+            &["dezember"],
+        ],
+        weekdays: [
+            "montag",
+            "dienstag",
+            "mittwoch",
+            "donnerstag",
+            "freitag",
+            "samstag",
+            "sonntag",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &[],
+        moods: ["super", "gut", "okay", "schlecht", "furchtbar"],
+        experimental: true,
+    },
+    PdfLocale {
+        code: "es",
+        name: "Spanish",
+        months: [
+            &["enero"],
+            &["febrero"],
+            &["marzo"],
+            &["abril"],
+            &["mayo"],
+            &["junio"],
+            &["julio"],
+            &["agosto"],
+            &["septiembre", "setiembre"],
+            &["octubre"],
+            &["noviembre"],
+            &["diciembre"],
+        ],
+        weekdays: [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &["de"],
+        moods: ["genial", "bien", "normal", "mal", "fatal"],
+        experimental: true,
+    },
+    PdfLocale {
+        code: "it",
+        name: "Italian",
+        months: [
+            &["gennaio"],
+            &["febbraio"],
+            &["marzo"],
+            &["aprile"],
+            &["maggio"],
+            &["giugno"],
+            &["luglio"],
+            &["agosto"],
+            &["settembre"],
+            &["ottobre"],
+            &["novembre"],
+            &["dicembre"],
+        ],
+        weekdays: [
+            "lunedì",
+            "martedì",
+            "mercoledì",
+            "giovedì",
+            "venerdì",
+            "sabato",
+            "domenica",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &[],
+        moods: ["ottimo", "bene", "così così", "male", "pessimo"],
+        experimental: true,
+    },
+    PdfLocale {
+        code: "pt",
+        name: "Portuguese",
+        months: [
+            &["janeiro"],
+            &["fevereiro"],
+            &["março"],
+            &["abril"],
+            &["maio"],
+            &["junho"],
+            &["julho"],
+            &["agosto"],
+            &["setembro"],
+            &["outubro"],
+            &["novembro"],
+            &["dezembro"],
+        ],
+        weekdays: [
+            "segunda-feira",
+            "terça-feira",
+            "quarta-feira",
+            "quinta-feira",
+            "sexta-feira",
+            "sábado",
+            "domingo",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &["de"],
+        moods: ["radiante", "bem", "mais ou menos", "mal", "horrível"],
+        experimental: true,
+    },
+    PdfLocale {
+        code: "nl",
+        name: "Dutch",
+        months: [
+            &["januari"],
+            &["februari"],
+            &["maart"],
+            &["april"],
+            &["mei"],
+            &["juni"],
+            &["juli"],
+            &["augustus"],
+            &["september"],
+            &["oktober"],
+            &["november"],
+            &["december"],
+        ],
+        weekdays: [
+            "maandag",
+            "dinsdag",
+            "woensdag",
+            "donderdag",
+            "vrijdag",
+            "zaterdag",
+            "zondag",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &[],
+        moods: ["super", "goed", "gaat wel", "slecht", "verschrikkelijk"],
+        experimental: true,
+    },
+    PdfLocale {
+        code: "pl",
+        name: "Polish",
+        months: [
+            &["stycznia", "styczeń"],
+            &["lutego", "luty"],
+            &["marca", "marzec"],
+            &["kwietnia", "kwiecień"],
+            &["maja", "maj"],
+            &["czerwca", "czerwiec"],
+            &["lipca", "lipiec"],
+            &["sierpnia", "sierpień"],
+            &["września", "wrzesień"],
+            &["października", "październik"],
+            &["listopada", "listopad"],
+            &["grudnia", "grudzień"],
+        ],
+        weekdays: [
+            "poniedziałek",
+            "wtorek",
+            "środa",
+            "czwartek",
+            "piątek",
+            "sobota",
+            "niedziela",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        date_fillers: &[],
+        moods: ["super", "dobrze", "tak sobie", "źle", "okropnie"],
+        experimental: true,
+    },
+    PdfLocale {
+        code: "ru",
+        name: "Russian",
+        months: [
+            &["января", "январь"],
+            &["февраля", "февраль"],
+            &["марта", "март"],
+            &["апреля", "апрель"],
+            &["мая", "май"],
+            &["июня", "июнь"],
+            &["июля", "июль"],
+            &["августа", "август"],
+            &["сентября", "сентябрь"],
+            &["октября", "октябрь"],
+            &["ноября", "ноябрь"],
+            &["декабря", "декабрь"],
+        ],
+        weekdays: [
+            "понедельник",
+            "вторник",
+            "среда",
+            "четверг",
+            "пятница",
+            "суббота",
+            "воскресенье",
+        ],
+        date_order: DateOrder::DayMonthYear,
+        // `2 августа 2022 г.`
+        date_fillers: &["г"],
+        moods: ["супер", "хорошо", "так себе", "плохо", "ужасно"],
+        experimental: true,
+    },
+];
+
+impl PdfLocale {
+    #[must_use]
+    pub fn from_code(code: &str) -> Option<&'static PdfLocale> {
+        LOCALES
+            .iter()
+            .find(|locale| locale.code.eq_ignore_ascii_case(code))
+    }
+
+    /// Month number, from 1
+    #[must_use]
+    pub fn month(&self, name: &str) -> Option<u32> {
+        let name = name.to_lowercase();
+        let idx = self
+            .months
+            .iter()
+            .position(|names| names.contains(&name.as_str()))?;
+        u32::try_from(idx + 1).ok()
+    }
+
+    /// Parses a date such as `August 2, 2022`, `2. August 2022` or `2 de agosto de 2022`
+    #[must_use]
+    pub fn parse_date(&self, date: &str) -> Option<NaiveDate> {
+        let date = date.to_lowercase();
+        let words = date
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+            .filter(|word| !word.is_empty() && !self.date_fillers.contains(word))
+            .collect::<Vec<_>>();
+
+        let [first, second, year] = words[..] else {
+            return None;
+        };
+        let (month, day) = match self.date_order {
+            DateOrder::MonthDayYear => (first, second),
+            DateOrder::DayMonthYear => (second, first),
+        };
+
+        NaiveDate::from_ymd_opt(year.parse().ok()?, self.month(month)?, day.parse().ok()?)
+    }
+
+    /// Id of the predefined mood, from 1 for rad to 5 for awful
+    #[must_use]
+    pub fn predefined_mood_idx(&self, name: &str) -> Option<i64> {
+        let name = name.trim().to_lowercase();
+        let idx = self.moods.iter().position(|mood| *mood == name)?;
+        i64::try_from(idx + 1).ok()
+    }
+}
+
+/// The language of the names written in CSV and Markdown files
+pub(crate) const ENGLISH: &PdfLocale = &LOCALES[0];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dates() {
+        let expected = NaiveDate::from_ymd_opt(2022, 8, 2);
+        let dates = [
+            ("en", "August 2, 2022"),
+            ("fr", "2 août 2022"),
+            ("de", "2. August 2022"),
+            ("es", "2 de agosto de 2022"),
+            ("it", "2 agosto 2022"),
+            ("pt", "2 de agosto de 2022"),
+            ("nl", "2 augustus 2022"),
+            ("pl", "2 sierpnia 2022"),
+            ("ru", "2 августа 2022 г."),
+        ];
+
+        for (code, date) in dates {
+            let locale = PdfLocale::from_code(code).unwrap();
+            assert_eq!(locale.parse_date(date), expected, "{code}: {date}");
+        }
+
+        let english = PdfLocale::from_code("en").unwrap();
+        assert_eq!(english.parse_date("2 août 2022"), None);
+        assert_eq!(english.parse_date("February 30, 2022"), None);
+    }
+
+    #[test]
+    fn test_every_locale_is_listed_once() {
+        for locale in LOCALES {
+            assert_eq!(PdfLocale::from_code(locale.code), Some(locale));
+        }
+    }
+
+    #[test]
+    fn test_mood_names_do_not_conflict() {
+        // the same word must be the same mood in every language, as the language may not be known
+        let predefined_mood_idx = |name| {
+            LOCALES
+                .iter()
+                .find_map(|locale| locale.predefined_mood_idx(name))
+        };
+        for locale in LOCALES {
+            for (idx, mood) in locale.moods.iter().enumerate() {
+                assert_eq!(predefined_mood_idx(mood), Some(idx as i64 + 1), "{mood}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_english_is_first() {
+        assert_eq!(ENGLISH.code, "en");
+    }
+}
@@ -1,120 +1,665 @@
-use std::env;
-use std::path::PathBuf;
+use std::io::{stdin, stdout, Write};
+use std::path::{Path, PathBuf};
 
-use color_eyre::eyre::{ContextCompat, Result};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use color_eyre::eyre::{bail, Result};
 
-use daylio_tools::{load_daylio, merge, store_daylio_backup, store_daylio_json};
+use daylio_tools::{
+    apply_time_zone, Backup, backup_existing, CustomMood, DayEntry, Daylio, ensure_not_input,
+    Format, import_pdf, infer_time_zone, load_backup_as, match_seconds, LOCALES, merge_backups,
+    merge_backups_three_way, MergeStrategy, NoteLayout, PdfLocale, PdfOptions, Resolution,
+    store_backup_as, store_markdown, TitleHeuristic, Tz,
+};
 
+/// Merge, anonymize and convert Daylio backups
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
 enum Command {
+    /// Merge several backups into a single one
     Merge {
+        /// Backups to merge, in order. Supports .daylio, .json, .csv and .pdf files, and folders of Markdown notes
+        #[arg(required = true, num_args = 2..)]
         input: Vec<PathBuf>,
-        output: PathBuf,
+        /// What to do with entries recorded at the same time, but with a different content
+        #[arg(long, value_enum, default_value_t = StrategyArg::KeepBoth)]
+        strategy: StrategyArg,
+        /// Print what has been merged, or write it as JSON with `--report=<PATH>`
+        #[arg(long, value_name = "PATH", require_equals = true)]
+        report: Option<Option<PathBuf>>,
+        /// Backup the inputs all come from, to tell the entries deleted in one input from the ones added in another
+        #[arg(long, value_name = "PATH")]
+        base: Option<PathBuf>,
+        #[command(flatten)]
+        input_format: InputFormat,
+        #[command(flatten)]
+        output: OutputArgs,
     },
+    /// Replace notes and names with random text, to share a backup
     Anonymize {
         input: PathBuf,
-        output: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
+        #[command(flatten)]
+        output: OutputArgs,
     },
+    /// Convert a backup to JSON, or to CSV with `--format csv`
     Extract {
         input: PathBuf,
-        output: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
+        #[command(flatten)]
+        output: OutputArgs,
     },
+    /// Convert a file to a backup that can be restored in the app
     Pack {
         input: PathBuf,
-        output: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Export the entries as Markdown notes, for Obsidian and similar tools
+    Markdown {
+        input: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Write one note per entry, instead of one per day
+        #[arg(long)]
+        per_entry: bool,
+    },
+    /// Check the references, ids and counts of a backup, without changing it
+    Validate {
+        input: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
     },
+    /// Fix what `validate` finds and renumber the ids, then print what has been changed
+    Repair {
+        input: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show what changes from one backup to another, such as a merge output, ignoring ids
+    Diff {
+        first: PathBuf,
+        second: PathBuf,
+        #[command(flatten)]
+        input_format: InputFormat,
+    },
+    /// Print a completion script for the given shell
+    Completions { shell: Shell },
+    /// Print the man page
+    Man,
 }
 
-fn parse_args() -> Result<Command> {
-    let args: Vec<String> = env::args().collect();
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Daylio,
+    Json,
+    Csv,
+    Pdf,
+    Markdown,
+}
 
-    let command = args.get(1).ok_or_else(|| {
-        color_eyre::eyre::eyre!(
-            "Missing command. Usage: daylio-tools <command> <input(s)> <output>"
-        )
-    })?;
+impl From<FormatArg> for Format {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Daylio => Format::Daylio,
+            FormatArg::Json => Format::Json,
+            FormatArg::Csv => Format::Csv,
+            FormatArg::Pdf => Format::Pdf,
+            FormatArg::Markdown => Format::Markdown,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StrategyArg {
+    /// Keep both entries, they show up twice in the app
+    KeepBoth,
+    /// Keep the entry of the first file
+    PreferFirst,
+    /// Keep the entry of the most recent backup
+    PreferNewest,
+    /// Keep a single entry, with the tags of both and the notes concatenated
+    Union,
+    /// Ask for each entry
+    Interactive,
+}
 
-    let get_single_in_out = || -> Result<(PathBuf, PathBuf)> {
-        let input = args
-            .get(2)
-            .ok_or_else(|| color_eyre::eyre::eyre!("Missing input path"))?;
-        let output = args
-            .get(3)
-            .ok_or_else(|| color_eyre::eyre::eyre!("Missing output path"))?;
+#[derive(Args)]
+struct InputFormat {
+    /// Format of the input files, instead of guessing it from their extension
+    #[arg(long, value_enum)]
+    input_format: Option<FormatArg>,
+    /// Language of the PDF inputs, such as `fr`, instead of detecting it. Experimental languages are only used when given
+    #[arg(long, value_parser = parse_pdf_locale)]
+    pdf_locale: Option<&'static PdfLocale>,
+    /// How to find note titles in PDF inputs
+    #[arg(long, value_enum, default_value_t = TitlesArg::Never)]
+    pdf_titles: TitlesArg,
+    /// Skip the entries of PDF inputs that cannot be read, instead of failing
+    #[arg(long)]
+    pdf_lenient: bool,
+    /// Time zone of the PDF, CSV and Markdown inputs, such as `Europe/Paris`. When merging, it is guessed from the other backups
+    #[arg(long, value_name = "ZONE", alias = "pdf-time-zone")]
+    time_zone: Option<Tz>,
+}
 
-        let input = PathBuf::from(input);
-        let output = PathBuf::from(output);
+#[derive(Clone, Copy, ValueEnum)]
+enum TitlesArg {
+    /// Keep titles in the notes
+    Never,
+    /// Use the first line of notes with several lines
+    FirstLine,
+    /// Use the first line when it is short and does not end a sentence
+    Guess,
+}
 
-        Ok((input, output))
-    };
+impl From<TitlesArg> for TitleHeuristic {
+    fn from(titles: TitlesArg) -> Self {
+        match titles {
+            TitlesArg::Never => TitleHeuristic::Never,
+            TitlesArg::FirstLine => TitleHeuristic::FirstLine,
+            TitlesArg::Guess => TitleHeuristic::Guess,
+        }
+    }
+}
+
+fn parse_pdf_locale(code: &str) -> Result<&'static PdfLocale, String> {
+    PdfLocale::from_code(code).ok_or_else(|| {
+        let codes = LOCALES.iter().map(|locale| locale.code).collect::<Vec<_>>();
+        format!("expected one of {}", codes.join(", "))
+    })
+}
+
+impl InputFormat {
+    fn format(&self, path: &Path) -> Result<Format> {
+        match self.input_format {
+            Some(format) => Ok(format.into()),
+            None => Format::from_path(path),
+        }
+    }
+
+    /// Sets the time zone of the inputs that only have local times from the entries of the other inputs,
+    /// unless it was given, then the seconds of the entries they share
+    fn match_local_times(&self, paths: &[PathBuf], backups: &mut [Backup]) -> Result<()> {
+        let is_local = paths
+            .iter()
+            .zip(backups.iter())
+            .map(|(path, backup)| Ok(has_local_times(self.format(path)?, &backup.daylio)))
+            .collect::<Result<Vec<_>>>()?;
+        if is_local.iter().all(|is_local| *is_local) {
+            return Ok(());
+        }
+
+        for (i, path) in paths.iter().enumerate().filter(|(i, _)| is_local[*i]) {
+            let mut imported = std::mem::take(&mut backups[i].daylio);
+            let others = backups
+                .iter()
+                .zip(&is_local)
+                .filter(|(_, is_local)| !**is_local)
+                .map(|(other, _)| &other.daylio);
+
+            if self.time_zone.is_none() {
+                let time_zone = others
+                    .clone()
+                    .find_map(|other| infer_time_zone(other, &imported));
+                if let Some(time_zone) = time_zone {
+                    eprintln!(
+                        "Entries of {} are in the {time_zone} time zone, guessed from the other backups",
+                        path.display()
+                    );
+                    apply_time_zone(&mut imported, time_zone);
+                } else {
+                    eprintln!(
+                        "No other backup has entries at the dates of {}, use --time-zone to give its time zone",
+                        path.display()
+                    );
+                }
+            }
+            for other in others {
+                match_seconds(&mut imported, other);
+            }
+
+            backups[i].daylio = imported;
+        }
+        Ok(())
+    }
+
+    fn load(&self, path: &Path) -> Result<Backup> {
+        let format = self.format(path)?;
+        if format == Format::Pdf {
+            let options = PdfOptions {
+                locale: self.pdf_locale,
+                titles: self.pdf_titles.into(),
+                lenient: self.pdf_lenient,
+                time_zone: self.time_zone,
+            };
+            let import = import_pdf(path, &options)?;
+            if !import.diagnostics.is_empty() {
+                eprintln!(
+                    "Skipped {} entries of {}:",
+                    import.diagnostics.len(),
+                    path.display()
+                );
+                for diagnostic in &import.diagnostics {
+                    eprintln!("{diagnostic}\n");
+                }
+            }
+            if !import.ambiguous_tags.is_empty() {
+                eprintln!(
+                    "Tags of {} that can be read in several ways:",
+                    path.display()
+                );
+                for ambiguity in &import.ambiguous_tags {
+                    eprintln!("{ambiguity}");
+                }
+                eprintln!();
+            }
+            if !import.verification.is_complete() {
+                eprintln!(
+                    "The statistics of {} do not match the imported entries:\n{}\n",
+                    path.display(),
+                    import.verification
+                );
+            }
+            return Ok(import.daylio.into());
+        }
+        let mut backup = load_backup_as(path, format)?;
+        if let Some(time_zone) = self
+            .time_zone
+            .filter(|_| has_local_times(format, &backup.daylio))
+        {
+            apply_time_zone(&mut backup.daylio, time_zone);
+        }
+        Ok(backup)
+    }
+}
+
+/// Whether the entries only have a local time, without the time zone of the app
+fn has_local_times(format: Format, daylio: &Daylio) -> bool {
+    match format {
+        Format::Pdf | Format::Csv => true,
+        // notes have an offset when their date has one, as in the notes we export
+        Format::Markdown => daylio
+            .day_entries
+            .iter()
+            .all(|entry| entry.time_zone_offset == 0),
+        Format::Daylio | Format::Json => false,
+    }
+}
 
-    match command.as_str() {
-        "merge" => {
-            let mut inputs = args.iter().skip(2).map(PathBuf::from).collect::<Vec<_>>();
-            let output = inputs.pop().wrap_err("Missing output file")?; // last one is output
+#[derive(Args)]
+struct OutputArgs {
+    /// File, or folder of Markdown notes, to write
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Format of the output file, instead of guessing it from its extension
+    #[arg(long, value_enum)]
+    format: Option<FormatArg>,
+    /// Overwrite the output file if it already exists, or write into a folder that is not empty
+    #[arg(long)]
+    force: bool,
+    /// Keep a timestamped .bak copy of the output file being overwritten
+    #[arg(long, requires = "force")]
+    backup: bool,
+    /// Do everything but writing files
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl OutputArgs {
+    /// Fails early, before spending time loading and merging
+    fn check(&self, inputs: &[PathBuf]) -> Result<()> {
+        ensure_not_input(&self.output, inputs)?;
+        if self.force || self.dry_run {
+            return Ok(());
+        }
+        if self.output.is_dir() {
+            let not_empty = self
+                .output
+                .read_dir()
+                .is_ok_and(|mut entries| entries.next().is_some());
+            if not_empty {
+                bail!(
+                    "{} is not empty, use --force to write into it",
+                    self.output.display()
+                );
+            }
+        } else if self.output.exists() {
+            bail!(
+                "{} already exists, use --force to overwrite it",
+                self.output.display()
+            );
+        }
+        Ok(())
+    }
 
-            if inputs.len() < 2 {
-                return Err(color_eyre::eyre::eyre!("Missing input files"));
+    /// Writes the backup, in `default` format if the extension is not a known one
+    fn write(&self, backup: &Backup, default: Format) -> Result<()> {
+        self.write_notes(backup, default, NoteLayout::PerDay)
+    }
+
+    /// Same as `write`, with the given layout if the output is Markdown notes
+    fn write_notes(&self, backup: &Backup, default: Format, layout: NoteLayout) -> Result<()> {
+        let format = self.format.map_or_else(
+            || match Format::from_path(&self.output) {
+                Ok(Format::Pdf) | Err(_) => default,
+                Ok(format) => format,
+            },
+            Into::into,
+        );
+
+        if self.dry_run {
+            println!(
+                "Would write {} entries to {} ({format:?})",
+                backup.daylio.day_entries.len(),
+                self.output.display()
+            );
+            return Ok(());
+        }
+        if self.backup {
+            if let Some(path) = backup_existing(&self.output)? {
+                println!("Saved the previous file to {}", path.display());
             }
+        }
+        match format {
+            Format::Markdown => store_markdown(backup, &self.output, layout),
+            _ => store_backup_as(backup, &self.output, format),
+        }
+    }
+}
+
+fn describe_entry(daylio: &Daylio, entry: &DayEntry) -> String {
+    let mood = daylio
+        .custom_moods
+        .iter()
+        .find(|mood| mood.id == entry.mood)
+        .map_or_else(String::new, CustomMood::name);
+    let tags = entry
+        .tags
+        .iter()
+        .filter_map(|id| daylio.tags.iter().find(|tag| tag.id == *id))
+        .map(|tag| tag.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} | {mood} | {tags}\n{}\n{}",
+        entry.year,
+        entry.month + 1, // month is 0-indexed in Daylio
+        entry.day,
+        entry.hour,
+        entry.minute,
+        entry.note_title,
+        entry.note
+    )
+}
+
+fn ask_resolution(
+    daylio: &Daylio,
+    first: Option<&DayEntry>,
+    second: Option<&DayEntry>,
+) -> Resolution {
+    if first.is_some() && second.is_some() {
+        println!("Two entries were recorded at the same time:");
+    } else {
+        println!("An entry was deleted in one file, and edited in the other one:");
+    }
+    let describe = |entry: Option<&DayEntry>| {
+        entry.map_or_else(|| "deleted".to_owned(), |e| describe_entry(daylio, e))
+    };
+    println!("[1] {}", describe(first));
+    println!("[2] {}", describe(second));
+
+    loop {
+        print!("Keep [1], [2], [b]oth, or [u]nion? ");
+        let _ = stdout().flush();
+
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return Resolution::KeepBoth; // no input, do not lose anything
+        }
 
-            Ok(Command::Merge {
-                input: inputs,
-                output,
-            })
-        }
-        "anonymize" => {
-            let args = get_single_in_out()?;
-            Ok(Command::Anonymize {
-                input: args.0,
-                output: args.1,
-            })
-        }
-        "extract" => {
-            let args = get_single_in_out()?;
-            Ok(Command::Extract {
-                input: args.0,
-                output: args.1,
-            })
-        }
-        "pack" => {
-            let args = get_single_in_out()?;
-            Ok(Command::Pack {
-                input: args.0,
-                output: args.1,
-            })
-        }
-        _ => Err(color_eyre::eyre::eyre!("Unknown command")),
+        match answer.trim() {
+            "1" => return Resolution::KeepFirst,
+            "2" => return Resolution::KeepSecond,
+            "b" => return Resolution::KeepBoth,
+            "u" => return Resolution::Union,
+            _ => {}
+        }
     }
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let command = parse_args()?;
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Merge {
+            input,
+            strategy,
+            report,
+            base,
+            input_format,
+            output,
+        } => {
+            let mut paths = input.clone();
+            paths.extend(base.clone());
+            output.check(&paths)?;
+            if let Some(Some(path)) = &report {
+                ensure_not_input(path, &paths)?;
+            }
+            let base = base.map(|path| input_format.load(&path)).transpose()?;
+
+            let mut backups = input
+                .iter()
+                .map(|path| input_format.load(path))
+                .collect::<Result<Vec<_>>>()?;
+            input_format.match_local_times(&input, &mut backups)?;
+
+            let mut backups = backups.into_iter();
+            let mut reference = backups.next().expect("at least two inputs");
+            let mut reports = Vec::new();
+
+            for other in backups {
+                let mut ask = ask_resolution;
+                let strategy = match strategy {
+                    StrategyArg::KeepBoth => MergeStrategy::KeepBoth,
+                    StrategyArg::PreferFirst => MergeStrategy::PreferFirst,
+                    StrategyArg::PreferNewest => MergeStrategy::PreferNewest,
+                    StrategyArg::Union => MergeStrategy::Union,
+                    StrategyArg::Interactive => MergeStrategy::Interactive(&mut ask),
+                };
 
-    match command {
-        Command::Merge { input, output } => {
-            let mut reference = load_daylio(&input[0])?;
+                let (merged, merge_report) = match &base {
+                    Some(base) => merge_backups_three_way(&base.daylio, reference, other, strategy),
+                    None => merge_backups(reference, other, strategy),
+                };
+                if report.is_none() && !merge_report.conflicts.is_empty() {
+                    eprintln!(
+                        "{} entries recorded at the same time differ, use --report for details",
+                        merge_report.conflicts.len()
+                    );
+                }
+                reference = merged;
+                reports.push(merge_report);
+            }
+            output.write(&reference, Format::Daylio)?;
 
-            for path in input.iter().skip(1) {
-                let other = load_daylio(path)?;
-                reference = merge(reference, other);
+            match report {
+                Some(Some(path)) if !output.dry_run => {
+                    let json = serde_json::to_string_pretty(&reports)?;
+                    std::fs::write(path, json)?;
+                }
+                Some(_) => {
+                    for (report, path) in reports.iter().zip(input.iter().skip(1)) {
+                        println!("Merging {}", path.display());
+                        println!("{report}");
+                    }
+                }
+                None => {}
             }
-            store_daylio_backup(&reference, &output)?;
         }
-        Command::Anonymize { input, output } => {
-            let mut daylio = load_daylio(&input)?;
-            daylio_tools::anonymize(&mut daylio);
-            store_daylio_backup(&daylio, &output)?;
+        Command::Anonymize {
+            input,
+            input_format,
+            output,
+        } => {
+            output.check(std::slice::from_ref(&input))?;
+            // photos would leak what the notes no longer tell
+            let mut backup = Backup::from(input_format.load(&input)?.daylio);
+            daylio_tools::anonymize(&mut backup.daylio);
+            output.write(&backup, Format::Daylio)?;
+        }
+        Command::Extract {
+            input,
+            input_format,
+            output,
+        } => {
+            output.check(std::slice::from_ref(&input))?;
+            let backup = input_format.load(&input)?;
+            output.write(&backup, Format::Json)?;
+        }
+        Command::Pack {
+            input,
+            input_format,
+            output,
+        } => {
+            output.check(std::slice::from_ref(&input))?;
+            let backup = input_format.load(&input)?;
+            output.write(&backup, Format::Daylio)?;
         }
-        Command::Extract { input, output } => {
-            let daylio = load_daylio(&input)?;
-            store_daylio_json(&daylio, &output)?;
+        Command::Markdown {
+            input,
+            input_format,
+            output,
+            per_entry,
+        } => {
+            output.check(std::slice::from_ref(&input))?;
+            let backup = input_format.load(&input)?;
+            let layout = if per_entry {
+                NoteLayout::PerEntry
+            } else {
+                NoteLayout::PerDay
+            };
+            output.write_notes(&backup, Format::Markdown, layout)?;
         }
-        Command::Pack { input, output } => {
-            let daylio = load_daylio(&input)?;
-            store_daylio_backup(&daylio, &output)?;
+        Command::Validate {
+            input,
+            input_format,
+        } => {
+            let backup = input_format.load(&input)?;
+            let problems = backup.daylio.validate();
+            if problems.is_empty() {
+                println!("No problems found in {}", input.display());
+            } else {
+                for problem in &problems {
+                    println!("{problem}");
+                }
+                bail!("Found {} problems in {}", problems.len(), input.display());
+            }
+        }
+        Command::Repair {
+            input,
+            input_format,
+            output,
+        } => {
+            output.check(std::slice::from_ref(&input))?;
+            let mut backup = input_format.load(&input)?;
+            let fixes = backup.daylio.repair();
+            if fixes.is_empty() {
+                println!("Nothing to repair in {}", input.display());
+            }
+            for fix in &fixes {
+                println!("{fix}");
+            }
+            output.write(&backup, Format::Daylio)?;
+        }
+        Command::Diff {
+            first,
+            second,
+            input_format,
+        } => {
+            let inputs = [first, second];
+            let mut backups = inputs
+                .iter()
+                .map(|path| input_format.load(path))
+                .collect::<Result<Vec<_>>>()?;
+            input_format.match_local_times(&inputs, &mut backups)?;
+
+            let diff = daylio_tools::diff(&backups[0].daylio, &backups[1].daylio);
+            if diff.is_empty() {
+                println!("No differences");
+            } else {
+                print!("{diff}");
+            }
+        }
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_owned();
+            clap_complete::generate(shell, &mut command, name, &mut stdout());
+        }
+        Command::Man => {
+            clap_mangen::Man::new(Cli::command()).render(&mut stdout())?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// The path of the report cannot be taken from the inputs
+    fn report_requires_equals() {
+        let cli = Cli::try_parse_from([
+            "daylio_tools",
+            "merge",
+            "--report",
+            "a.daylio",
+            "b.daylio",
+            "-o",
+            "out.daylio",
+        ])
+        .unwrap();
+        let Command::Merge { input, report, .. } = cli.command else {
+            panic!("not a merge");
+        };
+        assert_eq!(
+            input,
+            [PathBuf::from("a.daylio"), PathBuf::from("b.daylio")]
+        );
+        assert_eq!(report, Some(None));
+
+        let cli = Cli::try_parse_from([
+            "daylio_tools",
+            "merge",
+            "--report=report.json",
+            "a.daylio",
+            "b.daylio",
+            "-o",
+            "out.daylio",
+        ])
+        .unwrap();
+        let Command::Merge { report, .. } = cli.command else {
+            panic!("not a merge");
+        };
+        assert_eq!(report, Some(Some(PathBuf::from("report.json"))));
+    }
+}
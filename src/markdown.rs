@@ -0,0 +1,906 @@
+//! Exports a backup as Markdown notes with YAML front matter, for Obsidian and similar tools,
+//! and imports daily notes back.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat};
+use serde_json::Value;
+
+use crate::daylio_csv::{mood_name, NamedEntry, predefined_mood_name};
+use crate::{Backup, DayEntry, Daylio};
+
+/// Folder of the export where the photos and audio files are copied
+const ASSETS_DIR: &str = "assets";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoteLayout {
+    /// One note per day, named `YYYY-MM-DD.md` like Obsidian daily notes
+    #[default]
+    PerDay,
+    /// One note per entry, named `YYYY-MM-DD HHMM.md`
+    PerEntry,
+}
+
+/// A file of the export, with a path relative to the export folder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn ensure_new_line(markdown: &mut String) {
+    if !markdown.is_empty() && !markdown.ends_with('\n') {
+        markdown.push('\n');
+    }
+}
+
+/// Converts the light HTML of Daylio notes: line breaks, bold, italic, strikethrough and lists.
+/// Other tags have no Markdown equivalent and are dropped, keeping their text
+#[must_use]
+pub fn html_to_markdown(html: &str) -> String {
+    let mut markdown = String::new();
+    // `None` for bullet lists, the last number for numbered lists
+    let mut lists: Vec<Option<usize>> = Vec::new();
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        markdown.push_str(&decode_entities(&rest[..start]));
+        let tag = &rest[start + 1..start + len];
+        rest = &rest[start + len + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match (name.as_str(), closing) {
+            ("br", _) => markdown.push('\n'),
+            ("b" | "strong", _) => markdown.push_str("**"),
+            ("i" | "em", _) => markdown.push('*'),
+            ("s" | "strike" | "del", _) => markdown.push_str("~~"),
+            ("ul", false) => {
+                ensure_new_line(&mut markdown);
+                lists.push(None);
+            }
+            ("ol", false) => {
+                ensure_new_line(&mut markdown);
+                lists.push(Some(0));
+            }
+            ("ul" | "ol", true) => {
+                lists.pop();
+                ensure_new_line(&mut markdown);
+            }
+            ("li", false) => {
+                ensure_new_line(&mut markdown);
+                let depth = lists.len().saturating_sub(1);
+                markdown.push_str(&"  ".repeat(depth));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        let _ = write!(markdown, "{number}. ");
+                    }
+                    _ => markdown.push_str("- "),
+                }
+            }
+            ("p" | "div", _) => ensure_new_line(&mut markdown),
+            _ => {}
+        }
+    }
+    markdown.push_str(&decode_entities(rest));
+
+    markdown.trim_end().to_owned()
+}
+
+fn yaml_list(items: &[String]) -> String {
+    Value::from(items.to_vec()).to_string()
+}
+
+fn push_unique(items: &mut Vec<String>, item: String) {
+    if !item.is_empty() && !items.contains(&item) {
+        items.push(item);
+    }
+}
+
+/// Local time of the entry, with its offset
+fn timestamp(entry: &DayEntry) -> String {
+    let offset = i32::try_from(entry.time_zone_offset / 1000)
+        .ok()
+        .and_then(FixedOffset::east_opt)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+
+    DateTime::from_timestamp_millis(entry.datetime).map_or_else(
+        || entry.datetime.to_string(),
+        |date| {
+            date.with_timezone(&offset)
+                .to_rfc3339_opts(SecondsFormat::Secs, false)
+        },
+    )
+}
+
+fn date(entry: &DayEntry) -> String {
+    // month is 0-indexed in Daylio
+    format!("{}-{:02}-{:02}", entry.year, entry.month + 1, entry.day)
+}
+
+/// Names of everything the entries refer to, for the front matter
+#[derive(Default)]
+struct Properties {
+    timestamps: Vec<String>,
+    moods: Vec<String>,
+    mood_groups: Vec<String>,
+    tags: Vec<String>,
+    tag_groups: Vec<String>,
+}
+
+impl Properties {
+    fn add(&mut self, daylio: &Daylio, entry: &DayEntry) {
+        self.timestamps.push(timestamp(entry));
+        push_unique(&mut self.moods, mood_name(daylio, entry.mood));
+
+        let group = daylio
+            .custom_moods
+            .iter()
+            .find(|mood| mood.id == entry.mood)
+            .and_then(|mood| predefined_mood_name(mood.mood_group_id))
+            .unwrap_or_default();
+        push_unique(&mut self.mood_groups, group.to_owned());
+
+        for tag in entry
+            .tags
+            .iter()
+            .filter_map(|id| daylio.tags.iter().find(|tag| tag.id == *id))
+        {
+            push_unique(&mut self.tags, tag.name.clone());
+            if let Some(group) = daylio
+                .tag_groups
+                .iter()
+                .find(|group| group.id == tag.id_tag_group)
+            {
+                push_unique(&mut self.tag_groups, group.name.clone());
+            }
+        }
+    }
+}
+
+fn front_matter(fields: &[(&str, String)]) -> String {
+    let mut yaml = "---\n".to_owned();
+    for (key, value) in fields {
+        let _ = writeln!(yaml, "{key}: {value}");
+    }
+    yaml.push_str("---\n");
+    yaml
+}
+
+fn entry_properties(daylio: &Daylio, entry: &DayEntry) -> String {
+    let mut properties = Properties::default();
+    properties.add(daylio, entry);
+
+    front_matter(&[
+        ("date", timestamp(entry)),
+        ("mood", Value::from(properties.moods.concat()).to_string()),
+        (
+            "mood_group",
+            Value::from(properties.mood_groups.concat()).to_string(),
+        ),
+        ("tags", yaml_list(&properties.tags)),
+        ("tag_groups", yaml_list(&properties.tag_groups)),
+    ])
+}
+
+fn day_properties(daylio: &Daylio, entries: &[&DayEntry]) -> String {
+    let mut properties = Properties::default();
+    for entry in entries {
+        properties.add(daylio, entry);
+    }
+
+    front_matter(&[
+        ("date", date(entries[0])),
+        ("timestamps", yaml_list(&properties.timestamps)),
+        ("moods", yaml_list(&properties.moods)),
+        ("mood_groups", yaml_list(&properties.mood_groups)),
+        ("tags", yaml_list(&properties.tags)),
+        ("tag_groups", yaml_list(&properties.tag_groups)),
+    ])
+}
+
+/// Note body and links to the assets
+fn entry_content(entry: &DayEntry, asset_paths: &HashMap<i64, String>) -> String {
+    let mut content = html_to_markdown(&entry.note);
+    for path in entry.assets.iter().filter_map(|id| asset_paths.get(id)) {
+        if !content.is_empty() {
+            content.push_str("\n\n");
+        }
+        let _ = write!(content, "![]({path})");
+    }
+    content
+}
+
+/// Daylio names asset files after their checksum, without extension.
+/// Knowledge bases need one to show them
+fn asset_extension(data: &[u8]) -> &'static str {
+    match data {
+        [0xFF, 0xD8, ..] => ".jpg",
+        [0x89, b'P', b'N', b'G', ..] => ".png",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => ".m4a",
+        _ => "",
+    }
+}
+
+/// Copies the asset files, and returns their path by asset id
+fn export_assets(backup: &Backup, files: &mut Vec<ExportedFile>) -> HashMap<i64, String> {
+    let mut paths = HashMap::new();
+    for asset in &backup.daylio.assets {
+        let Some(file) = backup
+            .assets
+            .iter()
+            .find(|file| file.checksum() == asset.checksum)
+        else {
+            continue;
+        };
+
+        let path = format!(
+            "{ASSETS_DIR}/{}{}",
+            asset.checksum,
+            asset_extension(&file.data)
+        );
+        if !files.iter().any(|exported| exported.path == path) {
+            files.push(ExportedFile {
+                path: path.clone(),
+                data: file.data.clone(),
+            });
+        }
+        paths.insert(asset.id, path);
+    }
+    paths
+}
+
+fn unique_path(files: &[ExportedFile], name: &str) -> String {
+    let mut path = format!("{name}.md");
+    let mut i = 2;
+    while files.iter().any(|file| file.path == path) {
+        path = format!("{name} ({i}).md");
+        i += 1;
+    }
+    path
+}
+
+/// Converts the backup to Markdown notes, and copies the assets they link to
+#[must_use]
+pub fn export_markdown(backup: &Backup, layout: NoteLayout) -> Vec<ExportedFile> {
+    let daylio = &backup.daylio;
+    let mut files = Vec::new();
+    let asset_paths = export_assets(backup, &mut files);
+
+    let mut entries = daylio.day_entries.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.datetime);
+
+    match layout {
+        NoteLayout::PerEntry => {
+            for entry in entries {
+                let mut note = entry_properties(daylio, entry);
+                if !entry.note_title.is_empty() {
+                    let _ = write!(note, "# {}\n\n", entry.note_title);
+                }
+                let content = entry_content(entry, &asset_paths);
+                if !content.is_empty() {
+                    let _ = writeln!(note, "{content}");
+                }
+
+                let name = format!("{} {:02}{:02}", date(entry), entry.hour, entry.minute);
+                files.push(ExportedFile {
+                    path: unique_path(&files, &name),
+                    data: note.into_bytes(),
+                });
+            }
+        }
+        NoteLayout::PerDay => {
+            let mut days: Vec<Vec<&DayEntry>> = Vec::new();
+            for entry in entries {
+                match days.iter_mut().find(|day| date(day[0]) == date(entry)) {
+                    Some(day) => day.push(entry),
+                    None => days.push(vec![entry]),
+                }
+            }
+
+            for day in days {
+                let sections = day
+                    .iter()
+                    .map(|entry| {
+                        let mut section = format!("## {:02}:{:02}", entry.hour, entry.minute);
+                        if !entry.note_title.is_empty() {
+                            let _ = write!(section, " {}", entry.note_title);
+                        }
+
+                        let mut properties = Properties::default();
+                        properties.add(daylio, entry);
+                        let mut summary = properties.moods;
+                        summary.extend(properties.tags);
+                        if !summary.is_empty() {
+                            let _ = write!(section, "\n\n{}", summary.join(" · "));
+                        }
+
+                        let content = entry_content(entry, &asset_paths);
+                        if !content.is_empty() {
+                            let _ = write!(section, "\n\n{content}");
+                        }
+                        section
+                    })
+                    .collect::<Vec<_>>();
+                let note = format!(
+                    "{}\n{}\n",
+                    day_properties(daylio, &day),
+                    sections.join("\n\n")
+                );
+
+                files.push(ExportedFile {
+                    path: unique_path(&files, &date(day[0])),
+                    data: note.into_bytes(),
+                });
+            }
+        }
+    }
+
+    files
+}
+
+/// Mood of imported entries that do not have one
+const DEFAULT_MOOD: &str = "meh";
+
+/// Time of imported entries that only have a date
+const DEFAULT_TIME: (u32, u32) = (12, 0);
+
+/// Converts back the Markdown written by `html_to_markdown`: line breaks, bold and strikethrough
+#[must_use]
+pub fn markdown_to_html(markdown: &str) -> String {
+    markdown
+        .lines()
+        .map(|line| {
+            let line = line
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            let mut html = String::new();
+            for (i, part) in line.split("**").enumerate() {
+                if i > 0 {
+                    html.push_str(if i % 2 == 1 { "<b>" } else { "</b>" });
+                }
+                for (j, part) in part.split("~~").enumerate() {
+                    if j > 0 {
+                        html.push_str(if j % 2 == 1 { "<strike>" } else { "</strike>" });
+                    }
+                    html.push_str(part);
+                }
+            }
+            html
+        })
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        serde_json::from_str(value).unwrap_or_else(|_| value[1..value.len() - 1].to_owned())
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].replace("''", "'")
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Parses a front matter or property value: a scalar, or a `[a, b]` or `a, b` list
+fn parse_values(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = if value.starts_with('[') && !value.starts_with("[[") && value.ends_with(']') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    };
+    value
+        .split(',')
+        .map(|item| {
+            unquote(item)
+                .trim_start_matches('#')
+                .trim_start_matches("[[")
+                .trim_end_matches("]]")
+                .to_owned()
+        })
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// The YAML subset written by knowledge bases: `key: value`, `key: [a, b]` and block lists.
+/// Returns the fields and the rest of the note
+fn split_front_matter(content: &str) -> (HashMap<String, Vec<String>>, &str) {
+    let mut fields: HashMap<String, Vec<String>> = HashMap::new();
+
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (fields, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (fields, content);
+    };
+    let body = rest[end + 4..].trim_start_matches(['\r', '\n']);
+
+    let mut key = String::new();
+    for line in rest[..end].lines() {
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if line.starts_with([' ', '-']) {
+                fields
+                    .entry(key.clone())
+                    .or_default()
+                    .extend(parse_values(item));
+                continue;
+            }
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            key = name.trim().to_lowercase();
+            fields
+                .entry(key.clone())
+                .or_default()
+                .extend(parse_values(value));
+        }
+    }
+
+    (fields, body)
+}
+
+/// Logseq writes `key:: value` properties on the first lines of the page
+fn split_properties(body: &str, fields: &mut HashMap<String, Vec<String>>) -> String {
+    let mut lines = body.lines().peekable();
+    while let Some((name, value)) = lines
+        .peek()
+        .and_then(|line| line.trim_start_matches("- ").split_once(":: "))
+    {
+        fields
+            .entry(name.trim().to_lowercase())
+            .or_default()
+            .extend(parse_values(value));
+        lines.next();
+    }
+    lines.collect::<Vec<_>>().join("\n")
+}
+
+/// `#tag`, and `#[[multiple words]]` as written by Logseq
+fn inline_tags(text: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            let rest = &text[i + 1..];
+            let tag = if let Some(rest) = rest.strip_prefix("[[") {
+                rest.split_once("]]").map_or("", |(tag, _)| tag)
+            } else {
+                rest.split(|c: char| c.is_whitespace() || ",.;:!?()[]\"'".contains(c))
+                    .next()
+                    .unwrap_or_default()
+            };
+            // headings are followed by a space, and tags cannot be numbers
+            if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+                push_unique(&mut tags, tag.to_owned());
+            }
+        }
+        previous = c;
+    }
+    tags
+}
+
+fn is_embed(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("![") && line.ends_with(')'))
+        || (line.starts_with("![[") && line.ends_with("]]"))
+}
+
+/// Date and time of a note, and its offset in milliseconds
+fn parse_note_date(
+    fields: &HashMap<String, Vec<String>>,
+    name: &str,
+) -> Option<(NaiveDate, Option<NaiveTime>, i64)> {
+    if let Some(date) = fields.get("date").and_then(|values| values.first()) {
+        if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+            let offset = i64::from(date.offset().local_minus_utc()) * 1000;
+            return Some((date.date_naive(), Some(date.time()), offset));
+        }
+        for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+            if let Ok(date) = NaiveDateTime::parse_from_str(date, format) {
+                return Some((date.date(), Some(date.time()), 0));
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            return Some((date, None, 0));
+        }
+    }
+
+    // `2023-01-24`, `2023_01_24` for Logseq, or `2023-01-24 0959` for notes per entry
+    let date = NaiveDate::parse_from_str(&name.get(..10)?.replace('_', "-"), "%Y-%m-%d").ok()?;
+    let time = name
+        .get(10..)
+        .and_then(|time| NaiveTime::parse_from_str(time.trim(), "%H%M").ok());
+    Some((date, time, 0))
+}
+
+/// A `## 09:59 Title` heading, as written for notes per day
+fn parse_section_heading(line: &str) -> Option<(NaiveTime, String)> {
+    let heading = line.strip_prefix("## ")?;
+    let (time, title) = heading.split_once(' ').unwrap_or((heading, ""));
+    let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+    Some((time, title.trim().to_owned()))
+}
+
+fn first_value(fields: &HashMap<String, Vec<String>>, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|key| fields.get(*key).and_then(|values| values.first()))
+        .cloned()
+}
+
+/// Imports the daily notes. Notes without a date in their name or front matter are skipped.
+/// Each `## HH:MM` section becomes an entry, otherwise the whole note does
+pub(crate) fn parse_markdown_notes(notes: &[(String, String)]) -> Vec<NamedEntry> {
+    let mut entries = Vec::new();
+
+    for (name, content) in notes {
+        let (mut fields, body) = split_front_matter(content);
+        let body = split_properties(body, &mut fields);
+        let Some((date, time, time_zone_offset)) = parse_note_date(&fields, name) else {
+            continue;
+        };
+
+        let default_time = time.unwrap_or_else(|| {
+            NaiveTime::from_hms_opt(DEFAULT_TIME.0, DEFAULT_TIME.1, 0).unwrap_or_default()
+        });
+        let default_mood = first_value(&fields, &["mood", "moods"]);
+        let file_moods = fields.get("moods").cloned().unwrap_or_default();
+        let file_tags = fields.get("tags").cloned().unwrap_or_default();
+
+        // (time, title, lines)
+        let mut sections: Vec<(Option<NaiveTime>, String, Vec<&str>)> =
+            vec![(None, String::new(), vec![])];
+        for line in body.lines() {
+            if let Some((time, title)) = parse_section_heading(line) {
+                sections.push((Some(time), title, vec![]));
+            } else if let Some(section) = sections.last_mut() {
+                section.2.push(line);
+            }
+        }
+        if sections.len() > 1 && sections[0].2.iter().all(|line| line.trim().is_empty()) {
+            sections.remove(0);
+        }
+
+        for (time, mut title, lines) in sections {
+            let mut lines = lines
+                .into_iter()
+                .filter(|line| !is_embed(line))
+                .skip_while(|line| line.trim().is_empty())
+                .collect::<Vec<_>>();
+
+            if time.is_none() {
+                if let Some(heading) = lines.first().and_then(|line| line.strip_prefix("# ")) {
+                    heading.trim().clone_into(&mut title);
+                    lines.remove(0);
+                }
+            }
+
+            let mut mood = default_mood.clone();
+            let mut tags = file_tags.clone();
+
+            // `mood · tag · tag` summary line of notes per day
+            let text = lines.join("\n");
+            let mut text = text.trim();
+            let summary = text.split("\n\n").next().unwrap_or_default();
+            let mut names = summary.split(" · ");
+            if let Some(first) = names
+                .next()
+                .filter(|first| file_moods.iter().any(|mood| mood == first))
+            {
+                mood = Some(first.to_owned());
+                tags = names.map(ToOwned::to_owned).collect();
+                text = text[summary.len()..].trim();
+            }
+
+            for tag in inline_tags(text) {
+                push_unique(&mut tags, tag);
+            }
+
+            entries.push(NamedEntry {
+                date: NaiveDateTime::new(date, time.unwrap_or(default_time)),
+                time_zone_offset,
+                mood: mood.unwrap_or_else(|| DEFAULT_MOOD.to_owned()),
+                tags,
+                note_title: title,
+                note: markdown_to_html(text),
+            });
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Asset, AssetFile, CustomMood, Tag, TagGroup};
+
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        assert_eq!(
+            html_to_markdown(
+                "Hey<br><br>It's <b>bold</b>, <i>italic</i> &amp; <strike>gone</strike>"
+            ),
+            "Hey\n\nIt's **bold**, *italic* & ~~gone~~"
+        );
+        assert_eq!(
+            html_to_markdown(
+                "Todo:<ul><li>one</li><li>two<ol><li>a</li><li>b</li></ol></li></ul>Done"
+            ),
+            "Todo:\n- one\n- two\n  1. a\n  2. b\nDone"
+        );
+        assert_eq!(html_to_markdown("<u>kept</u> 1 < 2"), "kept 1 < 2");
+    }
+
+    fn sample() -> Backup {
+        let mut daylio = Daylio {
+            tags: vec![Tag {
+                id: 1,
+                name: "sport".to_owned(),
+                id_tag_group: 1,
+                ..Default::default()
+            }],
+            tag_groups: vec![TagGroup {
+                id: 1,
+                name: "Health".to_owned(),
+                ..Default::default()
+            }],
+            assets: vec![Asset {
+                id: 1,
+                checksum: "abc".to_owned(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        daylio.custom_moods.push(CustomMood {
+            id: 6,
+            custom_name: "love it".to_owned(),
+            mood_group_id: 1,
+            predefined_name_id: -1,
+            ..Default::default()
+        });
+        daylio.day_entries = vec![
+            DayEntry {
+                year: 2023,
+                month: 0,
+                day: 24,
+                hour: 9,
+                minute: 59,
+                datetime: 1_674_550_740_000,
+                time_zone_offset: 3_600_000,
+                mood: 6,
+                note_title: "Title".to_owned(),
+                note: "Body<br>line".to_owned(),
+                tags: vec![1],
+                assets: vec![1],
+                ..Default::default()
+            },
+            DayEntry {
+                year: 2023,
+                month: 0,
+                day: 24,
+                hour: 11,
+                minute: 36,
+                datetime: 1_674_556_560_000,
+                time_zone_offset: 3_600_000,
+                mood: 5,
+                ..Default::default()
+            },
+        ];
+
+        Backup {
+            daylio,
+            assets: vec![AssetFile {
+                path: "assets/photos/2023/1/abc".to_owned(),
+                data: vec![0xFF, 0xD8, 0xFF],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_export_per_entry() {
+        let files = export_markdown(&sample(), NoteLayout::PerEntry);
+
+        let paths = files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["assets/abc.jpg", "2023-01-24 0959.md", "2023-01-24 1136.md"]
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&files[1].data),
+            "---\n\
+            date: 2023-01-24T09:59:00+01:00\n\
+            mood: \"love it\"\n\
+            mood_group: \"rad\"\n\
+            tags: [\"sport\"]\n\
+            tag_groups: [\"Health\"]\n\
+            ---\n\
+            # Title\n\
+            \n\
+            Body\n\
+            line\n\
+            \n\
+            ![](assets/abc.jpg)\n"
+        );
+    }
+
+    #[test]
+    fn test_export_per_day() {
+        let files = export_markdown(&sample(), NoteLayout::PerDay);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].path, "2023-01-24.md");
+        assert_eq!(
+            String::from_utf8_lossy(&files[1].data),
+            "---\n\
+            date: 2023-01-24\n\
+            timestamps: [\"2023-01-24T09:59:00+01:00\",\"2023-01-24T11:36:00+01:00\"]\n\
+            moods: [\"love it\",\"awful\"]\n\
+            mood_groups: [\"rad\",\"awful\"]\n\
+            tags: [\"sport\"]\n\
+            tag_groups: [\"Health\"]\n\
+            ---\n\
+            \n\
+            ## 09:59 Title\n\
+            \n\
+            love it · sport\n\
+            \n\
+            Body\n\
+            line\n\
+            \n\
+            ![](assets/abc.jpg)\n\
+            \n\
+            ## 11:36\n\
+            \n\
+            awful\n"
+        );
+    }
+
+    fn summarize(daylio: &Daylio) -> Vec<(i64, i64, String, Vec<String>, String, String)> {
+        daylio
+            .day_entries
+            .iter()
+            .map(|entry| {
+                let tags = entry
+                    .tags
+                    .iter()
+                    .filter_map(|id| daylio.tags.iter().find(|tag| tag.id == *id))
+                    .map(|tag| tag.name.clone())
+                    .collect();
+                (
+                    entry.datetime,
+                    entry.time_zone_offset,
+                    mood_name(daylio, entry.mood),
+                    tags,
+                    entry.note_title.clone(),
+                    entry.note.clone(),
+                )
+            })
+            .collect()
+    }
+
+    fn import(files: &[ExportedFile]) -> Daylio {
+        let notes = files
+            .iter()
+            .filter_map(|file| {
+                let name = file.path.strip_suffix(".md")?;
+                Some((name.to_owned(), String::from_utf8(file.data.clone()).ok()?))
+            })
+            .collect::<Vec<_>>();
+        parse_markdown_notes(&notes).into()
+    }
+
+    #[test]
+    fn test_import_exported_notes() {
+        let mut backup = sample();
+        backup.daylio.day_entries[0].assets = vec![];
+        let backup = Backup::from(crate::merge(Daylio::default(), backup.daylio));
+
+        let per_entry = import(&export_markdown(&backup, NoteLayout::PerEntry));
+        assert_eq!(summarize(&per_entry), summarize(&backup.daylio));
+
+        // notes per day do not have the seconds and the time zone
+        let mut per_day = import(&export_markdown(&backup, NoteLayout::PerDay));
+        let mut expected = summarize(&backup.daylio);
+        for entry in &mut expected {
+            entry.0 += entry.1;
+            entry.1 = 0;
+        }
+        assert_eq!(summarize(&per_day), expected);
+
+        // they are guessed from the backup when merging
+        let time_zone = crate::infer_time_zone(&backup.daylio, &per_day).unwrap();
+        crate::apply_time_zone(&mut per_day, time_zone);
+        crate::match_seconds(&mut per_day, &backup.daylio);
+        assert_eq!(summarize(&per_day), summarize(&backup.daylio));
+    }
+
+    #[test]
+    fn test_import_obsidian_note() {
+        let note = "---\n\
+            mood: good\n\
+            tags:\n  - work\n  - \"#family\"\n\
+            ---\n\
+            # A **great** day\n\
+            \n\
+            Met #friends at the park, #1 day.\n\
+            ![[photo.jpg]]\n";
+
+        let entries = parse_markdown_notes(&[("2023-05-02".to_owned(), note.to_owned())]);
+
+        assert_eq!(
+            entries,
+            vec![NamedEntry {
+                date: NaiveDate::from_ymd_opt(2023, 5, 2)
+                    .unwrap()
+                    .and_hms_opt(12, 0, 0)
+                    .unwrap(),
+                time_zone_offset: 0,
+                mood: "good".to_owned(),
+                tags: vec!["work".to_owned(), "family".to_owned(), "friends".to_owned()],
+                note_title: "A **great** day".to_owned(),
+                note: "Met #friends at the park, #1 day.".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_import_logseq_journal() {
+        let note = "mood:: awful\n\
+            tags:: [[long day]], sport\n\
+            - Ran for <2 hours #[[personal best]]\n";
+
+        let entries = parse_markdown_notes(&[
+            ("2023_05_02".to_owned(), note.to_owned()),
+            ("Some page".to_owned(), "Not a journal".to_owned()),
+        ]);
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].mood, "awful");
+        assert_eq!(
+            entries[0].tags,
+            vec![
+                "long day".to_owned(),
+                "sport".to_owned(),
+                "personal best".to_owned()
+            ]
+        );
+        assert_eq!(entries[0].note, "- Ran for &lt;2 hours #[[personal best]]");
+    }
+
+    #[test]
+    fn test_markdown_to_html() {
+        assert_eq!(
+            markdown_to_html("It's **bold** & ~~gone~~\n\nNext"),
+            "It's <b>bold</b> &amp; <strike>gone</strike><br><br>Next"
+        );
+    }
+}
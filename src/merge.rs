@@ -1,5 +1,15 @@
-use crate::{DayEntry, NUMBER_OF_PREDEFINED_MOODS};
-use crate::daylio::{CustomMood, Daylio, Tag};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    Backup, Conflict, DayEntry, IdMapping, MergeReport, MergeStrategy, migrate, Resolution,
+    NUMBER_OF_PREDEFINED_MOODS,
+};
+use crate::daylio::{
+    Asset, CustomMood, Daylio, Goal, GoalEntry, GoalSuccessWeek, Tag, TagGroup, Unconfirmed,
+};
+
+/// Temporary ids start there, above the ids of the files
+const BIG_OFFSET: i64 = 1000;
 
 #[derive(Clone, Copy)]
 struct IdGenerator {
@@ -25,7 +35,8 @@ impl IdGenerator {
     }
 }
 
-trait ProjectEq<T> {
+/// What identifies an item across files, whatever its id
+pub(crate) trait ProjectEq<T> {
     fn project(&self) -> T;
 }
 
@@ -53,7 +64,60 @@ impl PartialEq for Tag {
     }
 }
 
+impl ProjectEq<String> for TagGroup {
+    fn project(&self) -> String {
+        self.name.to_lowercase()
+    }
+}
+
+impl ProjectEq<(String, Option<i64>)> for Goal {
+    fn project(&self) -> (String, Option<i64>) {
+        (self.name.to_lowercase(), self.id_icon)
+    }
+}
+
+impl ProjectEq<(i64, i64, i64, i64, i64, i64)> for GoalEntry {
+    fn project(&self) -> (i64, i64, i64, i64, i64, i64) {
+        (
+            self.goal_id,
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+        )
+    }
+}
+
+impl ProjectEq<(i64, i64, i64)> for GoalSuccessWeek {
+    fn project(&self) -> (i64, i64, i64) {
+        (self.goal_id, self.year, self.week)
+    }
+}
+
+impl ProjectEq<String> for Asset {
+    fn project(&self) -> String {
+        self.checksum.clone()
+    }
+}
+
 impl Daylio {
+    /// The goals, their check-ins and success weeks, when all of them have the expected fields.
+    /// Otherwise their ids cannot be followed, and they are left as they are
+    fn typed_goals(
+        &mut self,
+    ) -> Option<(
+        &mut Vec<Goal>,
+        &mut Vec<GoalEntry>,
+        &mut Vec<GoalSuccessWeek>,
+    )> {
+        Some((
+            self.goals.typed_mut()?,
+            self.goal_entries.typed_mut()?,
+            self.goal_success_weeks.typed_mut()?,
+        ))
+    }
+
     fn change_mood_id(day_entries: &mut [DayEntry], mood: &mut CustomMood, new_id: i64) {
         for entry in day_entries {
             if entry.mood == mood.id {
@@ -63,7 +127,12 @@ impl Daylio {
         mood.id = new_id;
     }
 
-    fn change_tag_id(day_entries: &mut [DayEntry], tag: &mut Tag, new_id: i64) {
+    fn change_tag_id(
+        day_entries: &mut [DayEntry],
+        goals: &mut Unconfirmed<Goal>,
+        tag: &mut Tag,
+        new_id: i64,
+    ) {
         for entry in day_entries {
             for i in 0..entry.tags.len() {
                 if entry.tags[i] == tag.id {
@@ -72,16 +141,84 @@ impl Daylio {
                 }
             }
         }
+        for goal in goals.typed_mut().into_iter().flatten() {
+            if goal.id_tag == Some(tag.id) {
+                goal.id_tag = Some(new_id);
+            }
+        }
         tag.id = new_id;
     }
 
+    fn change_tag_group_id(tags: &mut [Tag], group: &mut TagGroup, new_id: i64) {
+        for tag in tags {
+            if tag.id_tag_group == group.id {
+                tag.id_tag_group = new_id;
+            }
+        }
+        group.id = new_id;
+    }
+
+    fn change_goal_id(
+        goal_entries: &mut [GoalEntry],
+        success_weeks: &mut [GoalSuccessWeek],
+        goal: &mut Goal,
+        new_id: i64,
+    ) {
+        let link_id = goal.link_id();
+        for entry in goal_entries {
+            if entry.goal_id == link_id {
+                entry.goal_id = new_id;
+            }
+        }
+        for week in success_weeks {
+            if week.goal_id == link_id {
+                week.goal_id = new_id;
+            }
+        }
+        goal.id = new_id;
+        // both ids are kept equal, so that the references follow whichever the app reads
+        if goal.goal_id.is_some() {
+            goal.goal_id = Some(new_id);
+        }
+    }
+
+    fn change_asset_id(day_entries: &mut [DayEntry], asset: &mut Asset, new_id: i64) {
+        for entry in day_entries {
+            for i in 0..entry.assets.len() {
+                if entry.assets[i] == asset.id {
+                    entry.assets[i] = new_id;
+                    break;
+                }
+            }
+        }
+        asset.id = new_id;
+    }
+
     fn make_ids_distinct(&mut self, gen: &mut IdGenerator) {
         for mood in &mut self.custom_moods {
             Daylio::change_mood_id(&mut self.day_entries, mood, gen.next());
         }
 
         for tag in &mut self.tags {
-            Daylio::change_tag_id(&mut self.day_entries, tag, gen.next());
+            Daylio::change_tag_id(&mut self.day_entries, &mut self.goals, tag, gen.next());
+        }
+
+        for group in &mut self.tag_groups {
+            Daylio::change_tag_group_id(&mut self.tags, group, gen.next());
+        }
+
+        for asset in &mut self.assets {
+            Daylio::change_asset_id(&mut self.day_entries, asset, gen.next());
+        }
+
+        if let Some((goals, goal_entries, success_weeks)) = self.typed_goals() {
+            for goal in goals {
+                Daylio::change_goal_id(goal_entries, success_weeks, goal, gen.next());
+            }
+
+            for entry in goal_entries {
+                entry.id = gen.next();
+            }
         }
     }
 
@@ -105,14 +242,69 @@ impl Daylio {
         for i in 1..self.tags.len() {
             if self.tags[i - 1] == self.tags[i] {
                 let new_id = self.tags[i - 1].id;
-                Daylio::change_tag_id(&mut self.day_entries, &mut self.tags[i], new_id);
+                Daylio::change_tag_id(
+                    &mut self.day_entries,
+                    &mut self.goals,
+                    &mut self.tags[i],
+                    new_id,
+                );
                 self.tags[i].id = -1; // mark for deletion
             }
         }
 
         self.tags.retain(|tag| tag.id != -1);
 
-        // for entries
+        // for tag groups
+        self.tag_groups.sort_by_key(ProjectEq::project);
+
+        for i in 1..self.tag_groups.len() {
+            if self.tag_groups[i - 1].project() == self.tag_groups[i].project() {
+                let new_id = self.tag_groups[i - 1].id;
+                Daylio::change_tag_group_id(&mut self.tags, &mut self.tag_groups[i], new_id);
+                self.tag_groups[i].id = -1; // mark for deletion
+            }
+        }
+
+        self.tag_groups.retain(|group| group.id != -1);
+
+        // for assets: the same file is identified by its checksum
+        self.assets.sort_by_key(ProjectEq::project);
+
+        for i in 1..self.assets.len() {
+            if self.assets[i - 1].project() == self.assets[i].project() {
+                let new_id = self.assets[i - 1].id;
+                Daylio::change_asset_id(&mut self.day_entries, &mut self.assets[i], new_id);
+                self.assets[i].id = -1; // mark for deletion
+            }
+        }
+
+        self.assets.retain(|asset| asset.id != -1);
+
+        // for goals: the same goal may have been created on both devices
+        let Some((goals, goal_entries, success_weeks)) = self.typed_goals() else {
+            return;
+        };
+        goals.sort_by_key(ProjectEq::project);
+
+        for i in 1..goals.len() {
+            if goals[i - 1].project() == goals[i].project() {
+                let new_id = goals[i - 1].id;
+                Daylio::change_goal_id(goal_entries, success_weeks, &mut goals[i], new_id);
+                goals[i].id = -1; // mark for deletion
+            }
+        }
+
+        goals.retain(|goal| goal.id != -1);
+
+        // for goal check-ins, only one per goal at a given time is kept
+        goal_entries.sort_by_key(ProjectEq::project);
+        goal_entries.dedup_by(|a, b| a.project() == b.project());
+
+        success_weeks.sort_by_key(ProjectEq::project);
+        success_weeks.dedup_by(|a, b| a.project() == b.project());
+    }
+
+    fn remove_duplicate_entries(&mut self) {
         self.day_entries
             .sort_by_key(|x| (x.datetime, x.year, x.month));
 
@@ -169,10 +361,43 @@ impl Daylio {
         self.tags.sort_by_key(|x| x.created_at);
         let mut id_generator = IdGenerator::new(1);
         for (i, tag) in self.tags.iter_mut().enumerate() {
-            Daylio::change_tag_id(&mut self.day_entries, tag, id_generator.next());
+            Daylio::change_tag_id(
+                &mut self.day_entries,
+                &mut self.goals,
+                tag,
+                id_generator.next(),
+            );
             tag.order = i as i64 + 1;
         }
 
+        self.tag_groups.sort_by_key(|x| x.order);
+        let mut id_generator = IdGenerator::new(1);
+        for (i, group) in self.tag_groups.iter_mut().enumerate() {
+            Daylio::change_tag_group_id(&mut self.tags, group, id_generator.next());
+            group.order = i as i64 + 1;
+        }
+
+        self.assets.sort_by_key(|x| x.created_at);
+        let mut id_generator = IdGenerator::new(1);
+        for asset in &mut self.assets {
+            Daylio::change_asset_id(&mut self.day_entries, asset, id_generator.next());
+        }
+
+        if let Some((goals, goal_entries, success_weeks)) = self.typed_goals() {
+            goals.sort_by_key(|x| x.created_at);
+            let mut id_generator = IdGenerator::new(1);
+            for (i, goal) in goals.iter_mut().enumerate() {
+                Daylio::change_goal_id(goal_entries, success_weeks, goal, id_generator.next());
+                goal.order = goal.order.map(|_| i as i64 + 1);
+            }
+
+            goal_entries.sort_by_key(|x| (x.goal_id, x.year, x.month, x.day, x.hour, x.minute));
+            let mut id_generator = IdGenerator::new(1);
+            for entry in goal_entries {
+                entry.id = id_generator.next();
+            }
+        }
+
         self.day_entries
             .sort_by_key(|x| (-x.datetime, -x.year, -x.month));
         let mut id_generator = IdGenerator::new(1);
@@ -180,34 +405,229 @@ impl Daylio {
             entry.id = id_generator.next();
         }
     }
+
+    /// Same as `sanitize`, for a file whose ids may be duplicated or in any order.
+    /// Ids are made distinct first, so that renumbering one item does not catch the references of another
+    pub(crate) fn sanitize_ids(&mut self) {
+        let mut id_generator = IdGenerator::new(BIG_OFFSET);
+        self.make_ids_distinct(&mut id_generator);
+        self.sanitize();
+    }
 }
 
 /// Merges two daylio json files into one.
-/// We assume the files have version 15, but this is not checked.
+/// The older file is migrated to the version of the newer one, see [`migrate`].
 /// We keep everything from the first file, and add the new entries from the other files
 #[must_use]
-pub fn merge(mut daylio1: Daylio, mut daylio2: Daylio) -> Daylio {
-    const BIG_OFFSET: i64 = 1000;
+pub fn merge(daylio1: Daylio, daylio2: Daylio) -> Daylio {
+    merge_with_strategy(daylio1, daylio2, MergeStrategy::KeepBoth).0
+}
+
+/// Same as `merge`, but entries recorded at the same time with a different content
+/// are resolved according to `strategy`
+#[must_use]
+pub fn merge_with_strategy(
+    daylio1: Daylio,
+    daylio2: Daylio,
+    strategy: MergeStrategy,
+) -> (Daylio, Vec<Conflict>) {
+    let (merged, report) = merge_with_report(daylio1, daylio2, strategy);
+    (merged, report.conflicts)
+}
+
+/// Finds where each merged mood or tag comes from, using the same projection as the merge
+fn id_mappings<T, P>(
+    first: &[T],
+    second: &[T],
+    merged: &[T],
+    id: fn(&T) -> i64,
+    name: fn(&T) -> String,
+) -> Vec<IdMapping>
+where
+    T: ProjectEq<P>,
+    P: PartialEq,
+{
+    let find = |items: &[T], item: &T| items.iter().find(|x| x.project() == item.project()).map(id);
+
+    merged
+        .iter()
+        .map(|item| IdMapping {
+            name: name(item),
+            first_id: find(first, item),
+            second_id: find(second, item),
+            new_id: id(item),
+        })
+        .collect()
+}
+
+/// Counts, for each timestamp, the entries of both inputs and of the merged file.
+/// Entries of the second file are either added, or merged into an entry of the first file
+fn entry_origins(
+    first: &[DayEntry],
+    second: &[DayEntry],
+    merged: &[DayEntry],
+    conflicts: &[Conflict],
+) -> (Vec<i64>, Vec<i64>) {
+    let mut counts: BTreeMap<i64, [usize; 3]> = BTreeMap::new();
+    for (i, entries) in [first, second, merged].into_iter().enumerate() {
+        for entry in entries {
+            counts.entry(entry.datetime).or_default()[i] += 1;
+        }
+    }
+
+    let mut added = Vec::new();
+    let mut deduplicated = Vec::new();
+    for (datetime, [first, second, merged]) in counts {
+        let added_count = merged.saturating_sub(first).min(second);
+        let resolved = conflicts
+            .iter()
+            .filter(|x| x.datetime == datetime && x.resolution != Resolution::KeepBoth)
+            .count();
+        let deduplicated_count = second.saturating_sub(added_count + resolved);
+
+        added.extend(std::iter::repeat_n(datetime, added_count));
+        deduplicated.extend(std::iter::repeat_n(datetime, deduplicated_count));
+    }
+
+    (added, deduplicated)
+}
+
+/// Whether the second backup was made after the first one, for [`MergeStrategy::PreferNewest`]
+pub(crate) fn second_is_newer(daylio1: &Daylio, daylio2: &Daylio) -> bool {
+    daylio2.metadata.created_at > daylio1.metadata.created_at
+}
+
+/// Same as `merge_with_strategy`, but also reports what has been done
+#[must_use]
+pub fn merge_with_report(
+    mut daylio1: Daylio,
+    mut daylio2: Daylio,
+    strategy: MergeStrategy,
+) -> (Daylio, MergeReport) {
+    // the output has the schema of the newest file
+    let version = daylio1.version.max(daylio2.version);
+    migrate(&mut daylio1, version);
+    migrate(&mut daylio2, version);
+
+    let first_moods = daylio1.custom_moods.clone();
+    let second_moods = daylio2.custom_moods.clone();
+    let first_tags = daylio1.tags.clone();
+    let second_tags = daylio2.tags.clone();
+    let first_entries = daylio1.day_entries.clone();
+    let second_entries = daylio2.day_entries.clone();
 
     // first_pass: make sure we don't have any duplicates id
     let mut id_generator = IdGenerator::new(BIG_OFFSET);
     daylio1.make_ids_distinct(&mut id_generator);
     daylio2.make_ids_distinct(&mut id_generator);
 
+    // groups of the second file come after the ones of the first file
+    let last_group_order = daylio1.tag_groups.iter().map(|x| x.order).max();
+    for group in &mut daylio2.tag_groups {
+        group.order += last_group_order.unwrap_or(0);
+    }
+
+    let second_is_newer = second_is_newer(&daylio1, &daylio2);
+    let first_len = daylio1.day_entries.len();
+    let first_checksums = daylio1
+        .assets
+        .iter()
+        .map(|asset| asset.checksum.clone())
+        .collect::<HashSet<_>>();
+    let second_photo_size = daylio2.metadata.photos_size / daylio2.assets.len().max(1) as i64;
+
     let mut merged = daylio1;
     merged
         .custom_moods
         .append(&mut daylio2.custom_moods.clone());
     merged.tags.append(&mut daylio2.tags.clone());
+    merged.tag_groups.append(&mut daylio2.tag_groups.clone());
+    merged.assets.append(&mut daylio2.assets.clone());
+    // goals that could not be read are only kept from the first file
+    if let (
+        Some((goals, goal_entries, success_weeks)),
+        Some((goals2, goal_entries2, success_weeks2)),
+    ) = (merged.typed_goals(), daylio2.typed_goals())
+    {
+        goals.append(&mut goals2.clone());
+        goal_entries.append(&mut goal_entries2.clone());
+        success_weeks.append(&mut success_weeks2.clone());
+    }
     merged.day_entries.append(&mut daylio2.day_entries.clone());
+    // fields of newer versions of the app, the first file wins
+    for (key, value) in &daylio2.other {
+        if !merged.other.contains_key(key) {
+            merged.other.insert(key.clone(), value.clone());
+        }
+    }
 
     merged.remove_duplicates();
+    let conflicts = merged.resolve_conflicts(first_len, second_is_newer, strategy);
+    merged.remove_duplicate_entries();
     merged.sanitize();
 
     // update metadata
+    // we do not have the files here, so the size is only an estimate. See `merge_backups`
+    // the photos only in the second file count for its mean size, shared ones are already counted
     merged.metadata.number_of_entries = merged.day_entries.len() as i64;
-    merged.metadata.number_of_photos += daylio2.metadata.number_of_photos;
-    merged.metadata.photos_size += daylio2.metadata.photos_size;
+    merged.metadata.number_of_photos = merged.assets.len() as i64;
+    let added_photos = merged
+        .assets
+        .iter()
+        .filter(|asset| !first_checksums.contains(&asset.checksum))
+        .count();
+    merged.metadata.photos_size += second_photo_size * added_photos as i64;
+
+    let (added_entries, deduplicated_entries) = entry_origins(
+        &first_entries,
+        &second_entries,
+        &merged.day_entries,
+        &conflicts,
+    );
+
+    let report = MergeReport {
+        first_entries: first_entries.len(),
+        second_entries: second_entries.len(),
+        added_entries,
+        deduplicated_entries,
+        deleted_entries: Vec::new(),
+        conflicts,
+        moods: id_mappings(
+            &first_moods,
+            &second_moods,
+            &merged.custom_moods,
+            |mood| mood.id,
+            CustomMood::name,
+        ),
+        tags: id_mappings(
+            &first_tags,
+            &second_tags,
+            &merged.tags,
+            |tag| tag.id,
+            |tag| tag.name.clone(),
+        ),
+    };
+
+    (merged, report)
+}
 
-    merged
+/// Merges two backups, including their photos and audio files.
+/// Files are deduplicated by checksum, and the metadata is computed from the files that are kept
+#[must_use]
+pub fn merge_backups(
+    backup1: Backup,
+    backup2: Backup,
+    strategy: MergeStrategy,
+) -> (Backup, MergeReport) {
+    let (daylio, report) = merge_with_report(backup1.daylio, backup2.daylio, strategy);
+    let mut merged = Backup {
+        daylio,
+        assets: backup1.assets,
+    };
+    merged.assets.extend(backup2.assets);
+
+    merged.retain_referenced_assets();
+    merged.update_metadata();
+
+    (merged, report)
 }
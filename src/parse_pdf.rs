@@ -1,21 +1,22 @@
 //! This module parses the PDF file without any interpretation
 #![allow(dead_code)]
 
+use std::cmp::Reverse;
 use std::fmt::{Debug, Display};
-use std::mem;
 use std::path::Path;
 
 use chrono::NaiveDate;
-use color_eyre::eyre::{ContextCompat, WrapErr};
+use color_eyre::eyre::{bail, ContextCompat};
 use color_eyre::Result;
-use nom::{Finish, Parser};
-use nom::branch::alt;
+use nom::Finish;
 use nom::bytes::complete::{take_till, take_until};
-use nom::character::complete::{digit1, line_ending, multispace0, one_of, space0};
+use nom::character::complete::{digit1, line_ending, multispace0, one_of};
 use nom::combinator::{eof, map, map_res};
-use nom::multi::{count, many_till};
-use nom::sequence::{delimited, preceded, terminated, tuple};
-use pdftotext::pdftotext_layout;
+use nom::multi::{count, many0, many_till};
+use nom::sequence::{preceded, terminated, tuple};
+
+use crate::locale::{LOCALES, PdfLocale};
+use crate::pdf_text::extract_txt;
 
 type IResult<I, O> = nom::IResult<I, O, nom::error::VerboseError<I>>;
 
@@ -41,15 +42,12 @@ pub(crate) struct DayEntry {
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct ParsedPdf {
+    /// Language of the PDF, detected or chosen by the user
+    pub(crate) locale: &'static PdfLocale,
     pub(crate) stats: Vec<StatLine>,
     pub(crate) day_entries: Vec<DayEntry>,
-}
-
-fn extract_txt(pdf: &Path) -> Result<String> {
-    let path = pdf.to_str().wrap_err("Invalid path")?;
-    let txt = pdftotext_layout(path)?;
-
-    Ok(txt.join(""))
+    /// Entries that were skipped
+    pub(crate) diagnostics: Vec<PdfDiagnostic>,
 }
 
 fn read_line(input: &str) -> IResult<&str, &str> {
@@ -85,57 +83,13 @@ fn parse_stat_lines(input: &str) -> IResult<&str, Vec<StatLine>> {
     )(input)
 }
 
-/// differences between english and french:
-/// - month names
-/// - "month day, year" becomes "day month year" in french
-/// - 24 hour clock
-fn convert_french_date(date: &str) -> Option<String> {
-    let date = date.to_lowercase();
-    let month_dict = [
-        ("janvier", "january"),
-        ("février", "february"),
-        ("mars", "march"),
-        ("avril", "april"),
-        ("mai", "may"),
-        ("juin", "june"),
-        ("juillet", "july"),
-        ("août", "august"),
-        ("septembre", "september"),
-        ("octobre", "october"),
-        ("novembre", "november"),
-        ("décembre", "december"),
-    ];
-
-    if !month_dict.iter().any(|(french, _)| date.contains(french)) {
-        return Some(date); // not a french date
-    }
-
-    let mut date_parts = date.split_whitespace();
-    let day = date_parts.next()?;
-
-    let en_month = date_parts.next()?;
-    let month = month_dict
-        .iter()
-        .find(|(french, _)| *french == en_month)
-        .map(|(_, english)| english)?;
-
-    let year = date_parts.next()?;
-
-    Some(format!("{month} {day}, {year}"))
-}
-
-fn convert_language_date(date: &str) -> Option<String> {
-    convert_french_date(date)
-}
-
-/// Date looks like August 2, 2022
-fn string_to_date(date: &str) -> Result<NaiveDate> {
-    let date = convert_language_date(date).wrap_err("Invalid date")?;
-    NaiveDate::parse_from_str(&date, "%B %d, %Y").wrap_err(format!("Invalid date: {date}"))
-}
-
-fn parse_date(input: &str) -> IResult<&str, NaiveDate> {
-    map_res(take_until("  "), string_to_date)(input)
+/// Date looks like August 2, 2022, or 2 août 2022 in french
+fn parse_date<'a>(locale: &PdfLocale, input: &'a str) -> IResult<&'a str, NaiveDate> {
+    map_res(take_until("  "), |date: &str| {
+        locale
+            .parse_date(date)
+            .wrap_err(format!("Invalid {} date: {date}", locale.name))
+    })(input)
 }
 
 /// Example: ALL CAPS MOOD\n
@@ -148,30 +102,6 @@ fn parse_day_hour(input: &str) -> IResult<&str, &str> {
     read_line(input)
 }
 
-/// There may be a title, but there's no way for us to know if there is one
-/// So we count it as part of the body
-/// Pseudo-regex
-/// ```raw
-/// (\n{0, 1}([^\n]{1, n}, \n){1, n}\n{2, 3})
-/// ```
-fn parse_note_body(input: &str) -> IResult<&str, (Vec<&str>, Option<NaiveDate>)> {
-    // The body is a series of lines, separated by line endings
-    let body = alt((
-        parse_page_number.map(|_| None), // page numbers can be intertwined with the note
-        read_line.map(Some),
-    ));
-
-    let date_or_eof = alt((parse_date.map(Some), eof.map(|_| None)));
-
-    let body = many_till(body, date_or_eof).map(|(lines, date)| {
-        let no_empty_lines = lines.into_iter().flatten().filter(|l| !l.is_empty());
-
-        (no_empty_lines.collect(), date)
-    });
-
-    preceded(multispace0, body)(input)
-}
-
 /// A day entry looks like this:
 /// ```raw
 /// May 22, 2022              RAD
@@ -186,72 +116,338 @@ fn parse_note_body(input: &str) -> IResult<&str, (Vec<&str>, Option<NaiveDate>)>
 /// May 22, 2022              RAD
 /// Sunday 8 53 PM
 /// ```
-/// Seen as a pseudo regex:
-/// ```raw
-/// date {2, n}mood\nday hour\n(\n\n|\n{0, 1}([^\n]{1, n}, \n){1, n}\n{2, 3})
-/// ```
-/// body can also be ended by `\nEOF`
-fn parse_day_entries(input: &str) -> IResult<&str, Vec<DayEntry>> {
-    // So, we are in some kind of weird situation here.
-    // We use the date as a separator, as it is the only thing that is guaranteed to be there.
-    // But the date is the first thing we parse, so we're gonna be off by one.
-
-    let (input, mut prev_date) = map(parse_date, Some)(input)?;
-
-    let parse_day = map(
-        tuple((parse_mood, parse_day_hour, parse_note_body)),
-        |(mood, day_hour, (note, next_date))| {
-            prev_date?; // if there's no date, we're at the end of the file
-
-            let note = note.into_iter().map(ToOwned::to_owned).collect();
-
-            Some(DayEntry {
-                date: mem::replace(&mut prev_date, next_date).unwrap(),
-                mood: mood.to_owned(),
-                day_hour: day_hour.to_owned(),
-                note,
-            })
+/// There may be a title, but there's no way for us to know if there is one
+/// So we count it as part of the body
+fn parse_day_entry<'a>(locale: &PdfLocale, input: &'a str) -> IResult<&'a str, DayEntry> {
+    let parse_day = tuple((
+        |i| parse_date(locale, i),
+        parse_mood,
+        parse_day_hour,
+        many0(read_line),
+    ));
+
+    map(
+        terminated(parse_day, eof),
+        |(date, mood, day_hour, note)| DayEntry {
+            date,
+            mood: mood.to_owned(),
+            day_hour: day_hour.to_owned(),
+            note: note
+                .into_iter()
+                .filter(|l| !l.is_empty())
+                .map(ToOwned::to_owned)
+                .collect(),
         },
-    );
+    )(input)
+}
+
+/// Page numbers are alone on their line, and can be intertwined with notes.
+/// A note line with only a number is told apart by not being the next page
+fn is_page_number(line: &str, page: u32) -> bool {
+    line.trim().parse::<u32>().ok() == Some(page + 1)
+}
+
+fn starts_with_weekday(locale: &PdfLocale, line: &str) -> bool {
+    let day = line.split_whitespace().next().unwrap_or_default();
+    locale.weekdays.contains(&day.to_lowercase().as_str())
+}
+
+/// Example: Sunday 8 53 PM, or Sunday 20:53
+fn is_day_hour(locale: &PdfLocale, line: &str) -> bool {
+    let time = line
+        .split_whitespace()
+        .skip(1)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let time = time.replace(':', " ");
+    let is_number = |text: &str, len| {
+        (1..=len).contains(&text.len()) && text.bytes().all(|b| b.is_ascii_digit())
+    };
+    let is_time = match time.split(' ').collect::<Vec<_>>()[..] {
+        [hour, minute] => is_number(hour, 2) && is_number(minute, 2),
+        [hour, minute, suffix] => {
+            is_number(hour, 2)
+                && is_number(minute, 2)
+                && ["am", "pm"].contains(&suffix.to_lowercase().as_str())
+        }
+        _ => false,
+    };
+    is_time && starts_with_weekday(locale, line)
+}
+
+/// Example: May 22, 2022              RAD
+/// The date is not parsed, so that a broken one is reported instead of merged into the previous entry
+fn is_date_and_mood(line: &str) -> bool {
+    let Some((date, mood)) = line.trim().split_once("  ") else {
+        return false;
+    };
+    let mood = mood.trim();
+    // moods are written in capitals
+    date.bytes().any(|b| b.is_ascii_digit())
+        && !mood.is_empty()
+        && !mood.chars().any(char::is_lowercase)
+}
+
+/// Whether `line` is the first line of an entry. Notes may contain lines looking like one of the two lines,
+/// but not both: the time can be broken if the date is valid, and the other way around
+fn starts_entry(locale: &PdfLocale, line: &str, next: &str) -> bool {
+    if !is_date_and_mood(line) {
+        return false;
+    }
+    let valid_date = line
+        .trim()
+        .split_once("  ")
+        .is_some_and(|(date, _)| locale.parse_date(date).is_some());
+    is_day_hour(locale, next) || (valid_date && starts_with_weekday(locale, next))
+}
+
+/// Lines of a day entry in the extracted text
+struct EntryBlock<'a> {
+    page: u32,
+    /// First line, from 1
+    line: usize,
+    lines: Vec<&'a str>,
+}
+
+impl EntryBlock<'_> {
+    /// Explains why the entry could not be parsed
+    fn error(&self, locale: &PdfLocale) -> String {
+        let first_line = self.lines[0].trim();
+        match first_line.split_once("  ") {
+            None if self.lines.len() == 1 => "Unexpected text".to_owned(),
+            None => "Missing mood after the date".to_owned(),
+            Some((date, _)) if locale.parse_date(date).is_none() => {
+                format!("Invalid {} date: {date}", locale.name)
+            }
+            Some(_) => "Missing day and time after the date".to_owned(),
+        }
+    }
+
+    fn diagnostic(&self, message: String) -> PdfDiagnostic {
+        PdfDiagnostic {
+            page: self.page,
+            line: self.line,
+            text: self.lines.join("\n").trim_end().to_owned(),
+            message,
+        }
+    }
+}
 
-    let res = map(many_till(parse_day, eof), |(days, _)| days)(input);
-    res.map(|(input, days)| (input, days.into_iter().flatten().collect()))
+/// Splits the entries on their first line, the one before the day and hour.
+/// We cannot use the date alone, as a broken date would merge its entry into the previous one
+fn split_entries<'a>(locale: &PdfLocale, input: &'a str, first_line: usize) -> Vec<EntryBlock<'a>> {
+    let lines = input.lines().collect::<Vec<_>>();
+
+    let mut page = 1;
+    let mut blocks: Vec<EntryBlock> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if is_page_number(line, page) {
+            page += 1;
+            continue;
+        }
+
+        let starts_entry = lines
+            .get(i + 1)
+            .is_some_and(|next| starts_entry(locale, line, next));
+        match blocks.last_mut() {
+            Some(block) if !starts_entry => block.lines.push(line),
+            _ if starts_entry || !line.trim().is_empty() => blocks.push(EntryBlock {
+                page,
+                line: first_line + i,
+                lines: vec![line],
+            }),
+            _ => {} // blank lines before the first entry
+        }
+    }
+    blocks
 }
 
-fn parse_page_number(input: &str) -> IResult<&str, &str> {
-    delimited(space0, digit1, line_ending)(input)
+/// An entry of a PDF that could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfDiagnostic {
+    /// As printed at the top of the pages
+    pub page: u32,
+    /// Line of the extracted text where the entry starts, from 1
+    pub line: usize,
+    /// Extracted text of the entry
+    pub text: String,
+    pub message: String,
+}
+
+impl Display for PdfDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "page {}, line {}: {}\n{}",
+            self.page, self.line, self.message, self.text
+        )
+    }
+}
+
+/// Guesses the language from the months of the date range in the header, then from the mood names in the stats.
+/// Experimental languages are never guessed
+fn detect_locale(header: &[&str], stats: &[StatLine]) -> Result<(&'static PdfLocale, String)> {
+    let detectable = LOCALES.iter().filter(|locale| !locale.experimental);
+    // the line after "Daylio Export" looks like "April 27, 2022 - January 23, 2023"
+    let range = header.iter().find_map(|line| line.split_once(" - "));
+    let by_months = detectable
+        .clone()
+        .filter(|locale| {
+            range.is_some_and(|(from, to)| {
+                locale.parse_date(from).is_some() && locale.parse_date(to).is_some()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mood_count = |locale: &PdfLocale| {
+        stats
+            .iter()
+            .filter(|stat| locale.predefined_mood_idx(&stat.name).is_some())
+            .count()
+    };
+
+    let candidates = if by_months.is_empty() {
+        detectable.collect()
+    } else {
+        by_months.clone()
+    };
+    let best = candidates
+        .into_iter()
+        .min_by_key(|locale| Reverse(mood_count(locale)))
+        .wrap_err("No known language")?;
+    let moods = mood_count(best);
+
+    let range = range.map(|(from, to)| format!("`{from} - {to}`"));
+    let reason = match (range, by_months.is_empty(), moods) {
+        (Some(range), false, 0) => format!("guessed from the months of {range}"),
+        (Some(range), false, _) => {
+            format!("guessed from the months of {range} and {moods} mood names")
+        }
+        (Some(range), true, 1..) => format!(
+            "guessed from {moods} mood names, the months of {range} are in no known language"
+        ),
+        (None, _, 1..) => {
+            format!("guessed from {moods} mood names, the header has no date range")
+        }
+        (Some(range), true, 0) => bail!(
+            "Could not detect the language of the PDF: the months of {range} are in no known language, and no stat is a mood name. Known languages: {}",
+            known_locales()
+        ),
+        (None, _, 0) => bail!(
+            "Could not detect the language of the PDF: the header has no date range, and no stat is a mood name. Known languages: {}",
+            known_locales()
+        ),
+    };
+
+    Ok((best, reason))
+}
+
+fn known_locales() -> String {
+    let codes = |experimental: bool| {
+        LOCALES
+            .iter()
+            .filter(|locale| locale.experimental == experimental)
+            .map(|locale| locale.code)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "{}, and experimental ones that must be chosen: {}",
+        codes(false),
+        codes(true)
+    )
 }
 
 #[derive(Debug, Clone)]
 struct ParsePdfError {
-    json: String,
+    /// Language used to parse the PDF, and why
+    locale: Option<(&'static str, String)>,
+    details: String,
 }
 
 impl Display for ParsePdfError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Failed to parse PDF:\n{}", self.json)
+        match &self.locale {
+            Some((name, reason)) => {
+                write!(
+                    f,
+                    "Failed to parse PDF as {name} ({reason}):\n{}",
+                    self.details
+                )
+            }
+            None => write!(f, "Failed to parse PDF:\n{}", self.details),
+        }
     }
 }
 
 impl std::error::Error for ParsePdfError {}
 
-pub(crate) fn parse_pdf(path: &Path) -> Result<ParsedPdf> {
+/// Parses the PDF in the given language, or detects it if `None`.
+/// When `lenient`, entries that cannot be parsed are skipped and listed in [`ParsedPdf::diagnostics`]
+pub(crate) fn parse_pdf(
+    path: &Path,
+    locale: Option<&'static PdfLocale>,
+    lenient: bool,
+) -> Result<ParsedPdf> {
     let text = extract_txt(path)?;
-    let input = text.as_str();
-
-    let first_page = preceded(parse_header, parse_stat_lines);
+    parse_pdf_text(&text, locale, lenient)
+}
 
-    let mut parser = tuple((first_page, parse_day_entries));
+/// Parses the text of a PDF export, as extracted by `pdftotext -layout`
+pub(crate) fn parse_pdf_text(
+    input: &str,
+    locale: Option<&'static PdfLocale>,
+    lenient: bool,
+) -> Result<ParsedPdf> {
+    let mut first_page = tuple((parse_header, parse_stat_lines));
+
+    let (rest, (header, stats)) = first_page(input).finish().map_err(|e| ParsePdfError {
+        locale: None,
+        details: nom::error::convert_error(input, e),
+    })?;
+
+    let (locale, reason) = match locale {
+        Some(locale) => (locale, "chosen by the user".to_owned()),
+        None => detect_locale(&header, &stats)?,
+    };
+
+    let first_line = input[..input.len() - rest.len()].matches('\n').count() + 1;
+
+    let mut day_entries = Vec::new();
+    let mut diagnostics = Vec::new();
+    for block in split_entries(locale, rest, first_line) {
+        let text = block.lines.join("\n") + "\n";
+        let entry = parse_day_entry(locale, &text)
+            .finish()
+            .map_err(|_| block.error(locale))
+            .and_then(|(_, entry)| {
+                crate::analyze_pdf::parse_time(&entry.day_hour)
+                    .map(|_| entry)
+                    .map_err(|e| format!("Invalid time: {e}"))
+            });
+
+        match entry {
+            Ok(entry) => day_entries.push(entry),
+            Err(message) => diagnostics.push(block.diagnostic(message)),
+        }
+    }
 
-    parser(input)
-        .finish()
-        .map(|(_, (stats, day_entries))| ParsedPdf { stats, day_entries })
-        .map_err(|e| {
-            ParsePdfError {
-                json: nom::error::convert_error(input, e),
+    if !lenient {
+        if let Some(diagnostic) = diagnostics.first() {
+            return Err(ParsePdfError {
+                locale: Some((locale.name, reason)),
+                details: diagnostic.to_string(),
             }
-            .into()
-        })
+            .into());
+        }
+    }
+
+    Ok(ParsedPdf {
+        locale,
+        stats,
+        day_entries,
+        diagnostics,
+    })
 }
 
 #[cfg(test)]
@@ -264,13 +460,40 @@ pub(crate) mod tests {
 
     const SMALL_PDF_PATH_ENGLISH: &str = "tests/data/official/english.pdf";
 
+    fn english() -> &'static PdfLocale {
+        PdfLocale::from_code("en").unwrap()
+    }
+
+    fn string_to_date(date: &str) -> Result<NaiveDate> {
+        english().parse_date(date).wrap_err("Invalid date")
+    }
+
+    /// The pure-Rust backend does not pad columns exactly like poppler
+    fn normalize_columns(mut parsed: ParsedPdf) -> ParsedPdf {
+        if cfg!(feature = "poppler") {
+            return parsed;
+        }
+        for line in parsed
+            .day_entries
+            .iter_mut()
+            .flat_map(|e| e.note.iter_mut())
+        {
+            let columns = line.split("  ").map(str::trim).filter(|c| !c.is_empty());
+            *line = columns.collect::<Vec<_>>().join("  ");
+        }
+        parsed
+    }
+
     #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
     fn test_parse_small_pdf() -> Result<()> {
         // syntax:
 
-        let actual = parse_pdf(SMALL_PDF_PATH_ENGLISH.as_ref())?;
+        let actual = parse_pdf(SMALL_PDF_PATH_ENGLISH.as_ref(), None, false)?;
 
         let expected = ParsedPdf {
+            locale: english(),
+            diagnostics: vec![],
             stats: vec![
                 StatLine {
                     name: "rad".to_owned(),
@@ -376,7 +599,7 @@ pub(crate) mod tests {
             ],
         };
 
-        assert_eq!(actual, expected);
+        assert_eq!(normalize_columns(actual), normalize_columns(expected));
 
         Ok(())
     }
@@ -394,6 +617,8 @@ pub(crate) mod tests {
     }
 
     #[test]
+    // compares with the exact output of poppler
+    #[cfg(feature = "poppler")]
     fn extract_txt_test() {
         let txt = extract_txt(Path::new(TEST_PDF)).unwrap();
         let expected = get_txt();
@@ -472,8 +697,137 @@ pub(crate) mod tests {
     }
 
     #[test]
+    fn test_detect_locale() {
+        let txt = get_txt();
+        let (input, header) = parse_header(txt.as_str()).unwrap();
+        let stats = parse_stat_lines(input).unwrap().1;
+
+        let (locale, reason) = detect_locale(&header, &stats).unwrap();
+        assert_eq!(locale.code, "en");
+        assert_eq!(
+            reason,
+            "guessed from the months of `April 27, 2022 - January 23, 2023` and 4 mood names"
+        );
+
+        // Dutch is experimental
+        let header = ["Daylio Export", "2 april 2022 - 5 juni 2022"];
+        let stats = [StatLine::new("goed".to_owned(), 1)];
+        assert!(detect_locale(&header, &stats).is_err());
+
+        let header = ["Daylio Export", "2 avril 2022 - 5 juin 2022"];
+        assert_eq!(detect_locale(&header, &[]).unwrap().0.code, "fr");
+
+        let header = ["Daylio Export"];
+        let stats = [StatLine::new("mouais".to_owned(), 1)];
+        assert_eq!(detect_locale(&header, &stats).unwrap().0.code, "fr");
+    }
+
+    #[test]
+    fn test_detect_locale_failure() {
+        let header = ["Daylio Export", "2 Nisan 2022 - 5 Haziran 2022"];
+        let stats = [StatLine::new("harika".to_owned(), 1)];
+
+        let error = detect_locale(&header, &stats).unwrap_err().to_string();
+        assert!(error.starts_with("Could not detect the language of the PDF: the months of `2 Nisan 2022 - 5 Haziran 2022` are in no known language"), "{error}");
+    }
+
+    #[test]
+    fn test_parse_wrong_locale() {
+        let french = PdfLocale::from_code("fr").unwrap();
+
+        let error = parse_pdf_text(&get_txt(), Some(french), false)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Failed to parse PDF as French (chosen by the user):"),
+            "{error}"
+        );
+    }
+
+    /// The large PDF, with a broken date on page 1 and a broken time on page 2
+    fn get_broken_txt() -> String {
+        get_txt()
+            .replace(
+                "August 1, 2022              RAD",
+                "Agust 1, 2022              RAD",
+            )
+            .replace("Saturday 9 00 AM", "Saturday 9 h AM")
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let parsed = parse_pdf_text(&get_txt(), None, true).unwrap();
+        assert_eq!(parsed.day_entries.len(), 64);
+        assert_eq!(parsed.diagnostics, vec![]);
+
+        let parsed = parse_pdf_text(&get_broken_txt(), None, true).unwrap();
+        assert_eq!(parsed.day_entries.len(), 62);
+
+        let expected = vec![
+            PdfDiagnostic {
+                page: 1,
+                line: 40,
+                text: "Agust 1, 2022              RAD\nMonday 8 45 PM\n\n                     Note title 2 FFU\n                     Note 2 JBQ".to_owned(),
+                message: "Invalid English date: Agust 1, 2022".to_owned(),
+            },
+            PdfDiagnostic {
+                page: 2,
+                line: 62,
+                text: "July 30, 2022              MOOD 1 QBL\nSaturday 9 h AM\n\n                    Note title 5 VXG\n                    Note 5 AOT".to_owned(),
+                message: "Invalid time: input contains invalid characters".to_owned(),
+            },
+        ];
+        assert_eq!(parsed.diagnostics, expected);
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let error = parse_pdf_text(&get_broken_txt(), None, false)
+            .unwrap_err()
+            .to_string();
+
+        assert!(
+            error.starts_with("Failed to parse PDF as English (guessed from the months of `April 27, 2022 - January 23, 2023` and 4 mood names):\npage 1, line 40: "),
+            "{error}"
+        );
+    }
+
+    #[test]
+    /// Note lines starting with a weekday or made of a number are not entries or page numbers
+    fn test_parse_note_like_entry() {
+        let text = get_txt().replace(
+            "                     Note 2 JBQ\n",
+            "                     Note 2 JBQ\n                     Monday was hard\n                     42\n                     Sunday 9 00 AM\n",
+        );
+
+        let parsed = parse_pdf_text(&text, None, false).unwrap();
+        assert_eq!(parsed.day_entries.len(), 64);
+        assert_eq!(
+            parsed.day_entries[2].note,
+            vec![
+                "Note title 2 FFU",
+                "Note 2 JBQ",
+                "Monday was hard",
+                "42",
+                "Sunday 9 00 AM"
+            ]
+        );
+
+        // page numbers still follow
+        let broken = text.replace("Saturday 9 00 AM", "Saturday 9 h AM");
+        let parsed = parse_pdf_text(&broken, None, true).unwrap();
+        let pages = parsed
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.page)
+            .collect::<Vec<_>>();
+        assert_eq!(pages, vec![2]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
     fn test_parse_pdf() {
-        let parsed = parse_pdf(Path::new(TEST_PDF)).unwrap();
+        let parsed = parse_pdf(Path::new(TEST_PDF), None, false).unwrap();
         let expected_tags = expected_parsed_tags();
 
         let expected_entries = vec![
@@ -951,10 +1305,12 @@ pub(crate) mod tests {
         ];
 
         let expected = ParsedPdf {
+            locale: english(),
+            diagnostics: vec![],
             stats: expected_tags,
             day_entries: expected_entries,
         };
 
-        assert_eq!(parsed, expected);
+        assert_eq!(normalize_columns(parsed), normalize_columns(expected));
     }
 }
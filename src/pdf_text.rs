@@ -0,0 +1,268 @@
+//! Extracts the text of a PDF, keeping its layout like `pdftotext -layout` does.
+//! The parser relies on it: columns are separated by at least two spaces, and blocks by blank lines.
+
+use std::path::Path;
+
+use color_eyre::Result;
+
+/// Poppler is preferred when both backends are enabled, as the parser was written against its output
+#[cfg(feature = "poppler")]
+pub(crate) fn extract_txt(pdf: &Path) -> Result<String> {
+    use color_eyre::eyre::ContextCompat;
+
+    let path = pdf.to_str().wrap_err("Invalid path")?;
+    let txt = pdftotext::pdftotext_layout(path)?;
+
+    Ok(txt.join(""))
+}
+
+#[cfg(all(feature = "rust-pdf", not(feature = "poppler")))]
+pub(crate) fn extract_txt(pdf: &Path) -> Result<String> {
+    let doc = pdf_extract::Document::load(pdf)?;
+
+    let mut output = layout::LayoutOutput::default();
+    pdf_extract::output_doc(&doc, &mut output)?;
+
+    Ok(output.pages.concat())
+}
+
+#[cfg(not(any(feature = "poppler", feature = "rust-pdf")))]
+pub(crate) fn extract_txt(_pdf: &Path) -> Result<String> {
+    color_eyre::eyre::bail!(
+        "Reading PDF files is not supported by this build, enable the `poppler` or `rust-pdf` feature"
+    )
+}
+
+#[cfg(feature = "rust-pdf")]
+mod layout {
+    use pdf_extract::{MediaBox, OutputDev, OutputError, Transform};
+
+    /// Blank lines are capped, the parser only needs to tell blocks apart
+    const MAX_BLANK_LINES: usize = 3;
+
+    #[derive(Debug, Clone)]
+    struct Glyph {
+        x: f64,
+        /// Baseline, from the top of the page
+        y: f64,
+        width: f64,
+        size: f64,
+        text: String,
+    }
+
+    impl Glyph {
+        fn top(&self) -> f64 {
+            self.y - self.size * 0.7
+        }
+
+        fn end(&self) -> f64 {
+            self.x + self.width
+        }
+    }
+
+    /// Collects the glyphs of each page, then lays them out as text
+    #[derive(Default)]
+    pub(super) struct LayoutOutput {
+        page_height: f64,
+        glyphs: Vec<Glyph>,
+        pub(super) pages: Vec<String>,
+    }
+
+    impl OutputDev for LayoutOutput {
+        fn begin_page(
+            &mut self,
+            _page_num: u32,
+            media_box: &MediaBox,
+            _art_box: Option<(f64, f64, f64, f64)>,
+        ) -> Result<(), OutputError> {
+            self.page_height = media_box.ury - media_box.lly;
+            self.glyphs.clear();
+            Ok(())
+        }
+
+        fn end_page(&mut self) -> Result<(), OutputError> {
+            let glyphs = std::mem::take(&mut self.glyphs);
+            self.pages.push(layout_page(glyphs));
+            Ok(())
+        }
+
+        fn output_character(
+            &mut self,
+            trm: &Transform,
+            width: f64,
+            _spacing: f64,
+            font_size: f64,
+            char: &str,
+        ) -> Result<(), OutputError> {
+            // some fonts map spaces to NUL
+            let text = char.replace('\0', " ");
+            if text.trim().is_empty() {
+                return Ok(());
+            }
+
+            // same size as the square with the area of the transformed font size
+            let size_x = font_size * (trm.m11 + trm.m21);
+            let size_y = font_size * (trm.m12 + trm.m22);
+            let size = (size_x * size_y).abs().sqrt();
+
+            self.glyphs.push(Glyph {
+                x: trm.m31,
+                y: self.page_height - trm.m32,
+                width: width * size,
+                size,
+                text,
+            });
+            Ok(())
+        }
+
+        fn begin_word(&mut self) -> Result<(), OutputError> {
+            Ok(())
+        }
+
+        fn end_word(&mut self) -> Result<(), OutputError> {
+            Ok(())
+        }
+
+        fn end_line(&mut self) -> Result<(), OutputError> {
+            Ok(())
+        }
+    }
+
+    /// Groups glyphs whose heights overlap into lines, from top to bottom
+    fn split_lines(mut glyphs: Vec<Glyph>) -> Vec<Vec<Glyph>> {
+        glyphs.sort_by(|a, b| a.y.total_cmp(&b.y));
+
+        let mut lines: Vec<Vec<Glyph>> = Vec::new();
+        let mut bottom = f64::NEG_INFINITY;
+        for glyph in glyphs {
+            match lines.last_mut() {
+                Some(line) if glyph.top() < bottom => {
+                    bottom = bottom.max(glyph.y);
+                    line.push(glyph);
+                }
+                _ => {
+                    bottom = glyph.y;
+                    lines.push(vec![glyph]);
+                }
+            }
+        }
+
+        for line in &mut lines {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        }
+        lines
+    }
+
+    fn spaces(width: f64, char_width: f64) -> usize {
+        // the value is small and positive
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let count = (width / char_width).round().max(0.) as usize;
+        count
+    }
+
+    fn layout_page(glyphs: Vec<Glyph>) -> String {
+        if glyphs.is_empty() {
+            return String::new();
+        }
+
+        let left = glyphs.iter().map(|g| g.x).fold(f64::INFINITY, f64::min);
+        // the value is small
+        #[allow(clippy::cast_precision_loss)]
+        let char_width = glyphs.iter().map(|g| g.width).sum::<f64>() / glyphs.len() as f64;
+
+        let mut text = String::new();
+        let mut previous: Option<&Glyph> = None;
+        let lines = split_lines(glyphs);
+        for line in &lines {
+            // the first glyph gives the baseline and size of the line, as moods are bigger than dates
+            let first = &line[0];
+            if let Some(previous) = previous {
+                let line_height = first.size * 1.2;
+                let blank_lines = spaces(first.y - previous.y, line_height).saturating_sub(1);
+                text.push_str(&"\n".repeat(blank_lines.min(MAX_BLANK_LINES)));
+            }
+            previous = Some(first);
+
+            text.push_str(&" ".repeat(spaces(first.x - left, char_width)));
+            for (i, glyph) in line.iter().enumerate() {
+                if i > 0 {
+                    let gap = glyph.x - line[i - 1].end();
+                    if gap > glyph.size {
+                        // columns must be separated by two spaces at least
+                        text.push_str(&" ".repeat(spaces(gap, char_width).max(2)));
+                    } else if gap > glyph.size * 0.15 {
+                        text.push(' ');
+                    }
+                }
+                text.push_str(&glyph.text);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn word(x: f64, y: f64, size: f64, text: &str) -> Vec<Glyph> {
+            let width = size * 0.5;
+            text.chars()
+                .enumerate()
+                .filter(|(_, c)| *c != ' ')
+                .map(|(i, c)| Glyph {
+                    x: x + width * f64::from(u8::try_from(i).unwrap()),
+                    y,
+                    width,
+                    size,
+                    text: c.to_string(),
+                })
+                .collect()
+        }
+
+        #[test]
+        fn test_layout_page() {
+            let glyphs = [
+                word(100., 133., 36., "Daylio Export"),
+                word(1249., 129., 24., "1"),
+                word(100., 170., 24., "May 16, 2015 - January 25, 2023"),
+                word(150., 289., 21., "rad"),
+                word(430., 289., 21., "1×"),
+                word(550., 289., 21., "manger sain"),
+                word(150., 349., 21., "meh"),
+                word(430., 349., 21., "1×"),
+                word(100., 469., 24., "January 24, 2023"),
+                word(400., 475., 30., "AWFUL"),
+                word(100., 495., 18., "Tuesday 11 36 AM"),
+            ];
+            let text = layout_page(glyphs.concat());
+
+            // columns are separated by two spaces at least
+            let lines = text
+                .lines()
+                .map(|line| {
+                    let columns = line.split("  ").map(str::trim).filter(|c| !c.is_empty());
+                    columns.collect::<Vec<_>>().join(" | ")
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                lines,
+                [
+                    "Daylio Export | 1",
+                    "May 16, 2015 - January 25, 2023",
+                    "",
+                    "",
+                    "",
+                    "rad | 1× | manger sain",
+                    "",
+                    "meh | 1×",
+                    "",
+                    "",
+                    "",
+                    "January 24, 2023 | AWFUL",
+                    "Tuesday 11 36 AM",
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Fixes of the problems found by `Daylio::validate`, with a changelog
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use chrono::{Datelike, Timelike};
+
+use crate::validate::{calendar_mismatch, LOCAL_TIME_FORMAT};
+use crate::{CustomMood, Daylio, IdKind};
+
+/// Name of the mood given to the entries whose mood does not exist
+pub const PLACEHOLDER_MOOD: &str = "Missing mood";
+
+/// A change made by `Daylio::repair`. Entries, moods and tags are given by their id in the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// The tag was listed several times in the entry
+    DuplicateTag { entry: i64, tag: i64 },
+    /// The tag does not exist, it has been removed from the entry
+    DanglingTag { entry: i64, tag: i64 },
+    /// The tag group does not exist, the tag is now in no group
+    DanglingTagGroup { tag: i64, tag_group: i64 },
+    /// The mood does not exist, the entry now has the [`PLACEHOLDER_MOOD`]
+    PlaceholderMood { entry: i64, mood: i64 },
+    /// The mood had both a custom and a predefined name, the custom one is kept
+    CustomMoodName { mood: i64, name: String },
+    /// The date and time fields have been set from `datetime` and `time_zone_offset`
+    CalendarFields {
+        entry: i64,
+        fields: String,
+        expected: String,
+    },
+    /// Items have been given new ids, and sorted as the app expects
+    Renumbered { kind: IdKind, count: usize },
+    /// `metadata.number_of_entries` has been set to the number of entries
+    EntryCount { from: i64, to: usize },
+}
+
+impl Display for Fix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fix::DuplicateTag { entry, tag } => {
+                write!(f, "Entry {entry}: removed tag {tag}, listed several times")
+            }
+            Fix::DanglingTag { entry, tag } => {
+                write!(f, "Entry {entry}: removed tag {tag}, which does not exist")
+            }
+            Fix::DanglingTagGroup { tag, tag_group } => write!(
+                f,
+                "Tag {tag}: moved out of tag group {tag_group}, which does not exist"
+            ),
+            Fix::PlaceholderMood { entry, mood } => write!(
+                f,
+                "Entry {entry}: mood {mood} does not exist, replaced with \"{PLACEHOLDER_MOOD}\""
+            ),
+            Fix::CustomMoodName { mood, name } => write!(
+                f,
+                "Mood {mood}: removed the predefined name, `{name}` is kept"
+            ),
+            Fix::CalendarFields {
+                entry,
+                fields,
+                expected,
+            } => write!(
+                f,
+                "Entry {entry}: date and time changed from {fields} to {expected}"
+            ),
+            Fix::Renumbered { kind, count } => write!(f, "New {kind} ids for {count} items"),
+            Fix::EntryCount { from, to } => {
+                write!(f, "Metadata: number of entries changed from {from} to {to}")
+            }
+        }
+    }
+}
+
+/// Items of each collection, with what identifies them beside their id
+fn identities(daylio: &Daylio) -> Vec<(IdKind, HashSet<(i64, String)>)> {
+    vec![
+        (
+            IdKind::Mood,
+            daylio
+                .custom_moods
+                .iter()
+                .map(|mood| {
+                    let name = format!("{} {}", mood.predefined_name_id, mood.custom_name);
+                    (mood.id, name)
+                })
+                .collect(),
+        ),
+        (
+            IdKind::Tag,
+            daylio
+                .tags
+                .iter()
+                .map(|tag| (tag.id, tag.name.clone()))
+                .collect(),
+        ),
+        (
+            IdKind::TagGroup,
+            daylio
+                .tag_groups
+                .iter()
+                .map(|group| (group.id, group.name.clone()))
+                .collect(),
+        ),
+        (
+            IdKind::Entry,
+            daylio
+                .day_entries
+                .iter()
+                .map(|entry| (entry.id, entry.datetime.to_string()))
+                .collect(),
+        ),
+        (
+            IdKind::Asset,
+            daylio
+                .assets
+                .iter()
+                .map(|asset| (asset.id, asset.checksum.clone()))
+                .collect(),
+        ),
+        (
+            IdKind::Goal,
+            daylio
+                .goals
+                .typed()
+                .into_iter()
+                .flatten()
+                .map(|goal| (goal.id, goal.name.clone()))
+                .collect(),
+        ),
+    ]
+}
+
+impl Daylio {
+    /// Fixes what [`Daylio::validate`] finds, then renumbers everything with `sanitize`.
+    /// Returns what has been changed
+    pub fn repair(&mut self) -> Vec<Fix> {
+        let mut fixes = Vec::new();
+
+        for mood in &self.custom_moods {
+            if mood.predefined_name_id != -1 && !mood.custom_name.is_empty() {
+                fixes.push(Fix::CustomMoodName {
+                    mood: mood.id,
+                    name: mood.custom_name.clone(),
+                });
+            }
+        }
+
+        let tags = self.tags.iter().map(|tag| tag.id).collect::<HashSet<_>>();
+        let tag_groups = self
+            .tag_groups
+            .iter()
+            .map(|group| group.id)
+            .collect::<HashSet<_>>();
+        let moods = self
+            .custom_moods
+            .iter()
+            .map(|mood| mood.id)
+            .collect::<HashSet<_>>();
+
+        for tag in &mut self.tags {
+            // 0 is used by the app for the tags without a group
+            if tag.id_tag_group != 0 && !tag_groups.contains(&tag.id_tag_group) {
+                fixes.push(Fix::DanglingTagGroup {
+                    tag: tag.id,
+                    tag_group: tag.id_tag_group,
+                });
+                tag.id_tag_group = 0;
+            }
+        }
+
+        // above every id, including the missing ones that it replaces
+        let placeholder_id = moods
+            .iter()
+            .chain(self.day_entries.iter().map(|entry| &entry.mood))
+            .max()
+            .map_or(1, |id| id + 1);
+        let mut needs_placeholder = false;
+
+        for entry in &mut self.day_entries {
+            let mut seen = HashSet::new();
+            entry.tags.retain(|tag| {
+                if !seen.insert(*tag) {
+                    fixes.push(Fix::DuplicateTag {
+                        entry: entry.id,
+                        tag: *tag,
+                    });
+                    false
+                } else if !tags.contains(tag) {
+                    fixes.push(Fix::DanglingTag {
+                        entry: entry.id,
+                        tag: *tag,
+                    });
+                    false
+                } else {
+                    true
+                }
+            });
+
+            if !moods.contains(&entry.mood) {
+                fixes.push(Fix::PlaceholderMood {
+                    entry: entry.id,
+                    mood: entry.mood,
+                });
+                entry.mood = placeholder_id;
+                needs_placeholder = true;
+            }
+
+            if let Some((fields, local)) = calendar_mismatch(entry) {
+                fixes.push(Fix::CalendarFields {
+                    entry: entry.id,
+                    fields,
+                    expected: local.format(LOCAL_TIME_FORMAT).to_string(),
+                });
+                // month is 0-indexed in Daylio
+                entry.year = i64::from(local.year());
+                entry.month = i64::from(local.month0());
+                entry.day = i64::from(local.day());
+                entry.hour = i64::from(local.hour());
+                entry.minute = i64::from(local.minute());
+            }
+        }
+
+        if needs_placeholder {
+            self.custom_moods.push(CustomMood {
+                id: placeholder_id,
+                custom_name: PLACEHOLDER_MOOD.to_owned(),
+                predefined_name_id: -1,
+                // the group of the missing mood is unknown, "meh" is the least wrong
+                mood_group_id: 3,
+                icon_id: 1,
+                ..Default::default()
+            });
+        }
+
+        let before = identities(self);
+        self.sanitize_ids();
+        for ((kind, before), (_, after)) in before.iter().zip(identities(self)) {
+            let count = after.difference(before).count();
+            if count > 0 {
+                fixes.push(Fix::Renumbered { kind: *kind, count });
+            }
+        }
+
+        let count = self.day_entries.len();
+        if usize::try_from(self.metadata.number_of_entries).ok() != Some(count) {
+            fixes.push(Fix::EntryCount {
+                from: self.metadata.number_of_entries,
+                to: count,
+            });
+            self.metadata.number_of_entries = count as i64;
+        }
+
+        fixes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DayEntry, Tag};
+
+    use super::*;
+
+    #[test]
+    fn test_repair() {
+        let hour = 3_600_000;
+        let tag = |id, created_at| Tag {
+            id,
+            name: format!("tag {id}"),
+            created_at,
+            id_tag_group: 1,
+            ..Default::default()
+        };
+        let mut daylio = Daylio {
+            // renumbering in place would swap their references
+            tags: vec![tag(2, 1), tag(1, 2)],
+            day_entries: vec![
+                DayEntry {
+                    id: 1,
+                    // 2023-01-24 08:30 in UTC+1
+                    minute: 30,
+                    hour: 8,
+                    day: 24,
+                    month: 0,
+                    year: 2023,
+                    datetime: 1_674_545_400_000,
+                    time_zone_offset: hour,
+                    mood: 1,
+                    tags: vec![1, 2, 1, 7],
+                    ..Default::default()
+                },
+                DayEntry {
+                    id: 2,
+                    // 2023-01-23 10:00 in UTC+1, but written at 9:00
+                    minute: 0,
+                    hour: 9,
+                    day: 23,
+                    month: 0,
+                    year: 2023,
+                    datetime: 1_674_464_400_000,
+                    time_zone_offset: hour,
+                    mood: 42,
+                    tags: vec![1],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        daylio.tags[1].id_tag_group = 5;
+
+        let fixes = daylio.repair();
+        let expected = vec![
+            Fix::DanglingTagGroup {
+                tag: 1,
+                tag_group: 5,
+            },
+            Fix::DuplicateTag { entry: 1, tag: 1 },
+            Fix::DanglingTag { entry: 1, tag: 7 },
+            Fix::PlaceholderMood { entry: 2, mood: 42 },
+            Fix::CalendarFields {
+                entry: 2,
+                fields: "2023-01-23 09:00".to_owned(),
+                expected: "2023-01-23 10:00".to_owned(),
+            },
+            Fix::Renumbered {
+                kind: IdKind::Mood,
+                count: 1,
+            },
+            Fix::Renumbered {
+                kind: IdKind::Tag,
+                count: 2,
+            },
+            Fix::EntryCount { from: 0, to: 2 },
+        ];
+        assert_eq!(fixes, expected);
+        assert_eq!(daylio.validate(), vec![]);
+
+        // the tags were sorted by creation, and the references followed them
+        let names = |entry: &DayEntry| {
+            entry
+                .tags
+                .iter()
+                .map(|id| {
+                    daylio
+                        .tags
+                        .iter()
+                        .find(|tag| tag.id == *id)
+                        .unwrap()
+                        .name
+                        .clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&daylio.day_entries[0]), vec!["tag 1", "tag 2"]);
+        assert_eq!(names(&daylio.day_entries[1]), vec!["tag 1"]);
+        assert_eq!(daylio.day_entries[1].hour, 10);
+        let mood = daylio
+            .custom_moods
+            .iter()
+            .find(|mood| mood.id == daylio.day_entries[1].mood)
+            .unwrap();
+        assert_eq!(mood.custom_name, PLACEHOLDER_MOOD);
+
+        // nothing left to do
+        assert_eq!(daylio.repair(), vec![]);
+    }
+}
@@ -0,0 +1,106 @@
+//! Summary of what `merge_with_report` and `merge_three_way` did
+
+use std::fmt::{Display, Formatter};
+
+use chrono::DateTime;
+use serde_derive::Serialize;
+
+use crate::Conflict;
+
+/// How an id of the input files has been rewritten
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IdMapping {
+    pub name: String,
+    /// Id in the first file, if it was there
+    pub first_id: Option<i64>,
+    /// Id in the second file, if it was there
+    pub second_id: Option<i64>,
+    pub new_id: i64,
+}
+
+impl IdMapping {
+    /// The mood or tag was in both files, and they have been unified
+    #[must_use]
+    pub fn is_unified(&self) -> bool {
+        self.first_id.is_some() && self.second_id.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+    pub first_entries: usize,
+    pub second_entries: usize,
+    /// Timestamps of the entries of the second file added to the first one
+    pub added_entries: Vec<i64>,
+    /// Timestamps of the entries of the second file that were already in the first one
+    pub deduplicated_entries: Vec<i64>,
+    /// Timestamps of the entries of the common ancestor deleted in one of the files, see `merge_three_way`
+    pub deleted_entries: Vec<i64>,
+    pub conflicts: Vec<Conflict>,
+    pub moods: Vec<IdMapping>,
+    pub tags: Vec<IdMapping>,
+}
+
+fn format_datetime(datetime: i64) -> String {
+    DateTime::from_timestamp_millis(datetime).map_or_else(
+        || datetime.to_string(),
+        |date| date.format("%Y-%m-%d %H:%M UTC").to_string(),
+    )
+}
+
+fn format_id(id: Option<i64>) -> String {
+    id.map_or_else(|| "-".to_owned(), |id| id.to_string())
+}
+
+fn write_mappings(f: &mut Formatter<'_>, title: &str, mappings: &[IdMapping]) -> std::fmt::Result {
+    writeln!(f, "{title} (first, second -> merged):")?;
+    for mapping in mappings {
+        let unified = if mapping.is_unified() {
+            " (unified)"
+        } else {
+            ""
+        };
+        writeln!(
+            f,
+            "  {}: {}, {} -> {}{unified}",
+            mapping.name,
+            format_id(mapping.first_id),
+            format_id(mapping.second_id),
+            mapping.new_id,
+        )?;
+    }
+    Ok(())
+}
+
+impl Display for MergeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Entries: {} in the first file, {} in the second file",
+            self.first_entries, self.second_entries
+        )?;
+        writeln!(f, "Added entries: {}", self.added_entries.len())?;
+        writeln!(
+            f,
+            "Deduplicated entries: {}",
+            self.deduplicated_entries.len()
+        )?;
+        if !self.deleted_entries.is_empty() {
+            writeln!(f, "Deleted entries: {}", self.deleted_entries.len())?;
+        }
+
+        writeln!(f, "Conflicting entries: {}", self.conflicts.len())?;
+        for conflict in &self.conflicts {
+            writeln!(
+                f,
+                "  {}: {} differ, {:?}",
+                format_datetime(conflict.datetime),
+                conflict.fields.join(", "),
+                conflict.resolution
+            )?;
+        }
+
+        write_mappings(f, "Moods", &self.moods)?;
+        write_mappings(f, "Tags", &self.tags)
+    }
+}
@@ -0,0 +1,148 @@
+//! Versions of the backup format. The app writes its schema version in each backup, and newer
+//! versions may add or change fields. Backups are checked on load, and upgraded when merged with a
+//! newer one.
+
+use color_eyre::eyre::{bail, WrapErr};
+use color_eyre::Result;
+use serde_derive::Deserialize;
+
+use crate::Daylio;
+
+/// Changes of the backup format from `from` to the next version
+struct Migration {
+    from: i64,
+    apply: fn(&mut Daylio),
+}
+
+/// Every migration, in order, from [`OLDEST_VERSION`] to [`LATEST_VERSION`].
+/// Fields that the types do not know yet are in the `other` maps, where migrations can find them
+const MIGRATIONS: &[Migration] = &[];
+
+/// Oldest version of the backups this tool was written for
+pub const OLDEST_VERSION: i64 = 15;
+/// Newest version this tool knows how to write
+pub const LATEST_VERSION: i64 = OLDEST_VERSION + MIGRATIONS.len() as i64;
+
+#[derive(Deserialize)]
+struct VersionOnly {
+    version: i64,
+}
+
+/// Reads the version of a backup, before parsing the rest which depends on it
+pub(crate) fn read_version(json: &[u8]) -> Result<i64> {
+    let VersionOnly { version } =
+        serde_json::from_slice(json).wrap_err("Failed to read the version of the backup")?;
+    Ok(version)
+}
+
+/// Refuses the versions that no migration leads to, as their fields may mean something else
+pub fn check_version(version: i64) -> Result<()> {
+    if !(OLDEST_VERSION..=LATEST_VERSION).contains(&version) {
+        let known = if OLDEST_VERSION == LATEST_VERSION {
+            format!("version {OLDEST_VERSION}")
+        } else {
+            format!("versions {OLDEST_VERSION} to {LATEST_VERSION}")
+        };
+        bail!("Backups of version {version} are not supported, only {known}. An update of this tool may be needed");
+    }
+    Ok(())
+}
+
+fn migrate_with(daylio: &mut Daylio, version: i64, migrations: &[Migration]) {
+    for migration in migrations {
+        if migration.from >= daylio.version && migration.from < version {
+            (migration.apply)(daylio);
+            daylio.version = migration.from + 1;
+            // the app writes the same number in both
+            daylio.metadata.android_version = daylio.version;
+        }
+    }
+}
+
+/// Upgrades the backup to `version`. Nothing is done if it is already as recent, or if a version
+/// is unknown, as [`check_version`] refuses those when loading
+pub fn migrate(daylio: &mut Daylio, version: i64) {
+    if check_version(daylio.version).is_ok() && check_version(version).is_ok() {
+        migrate_with(daylio, version, MIGRATIONS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_check_version() {
+        assert!(check_version(15).is_ok());
+        assert!(check_version(LATEST_VERSION).is_ok());
+
+        let error = check_version(LATEST_VERSION + 1).unwrap_err().to_string();
+        assert!(error.starts_with(&format!(
+            "Backups of version {} are not supported",
+            LATEST_VERSION + 1
+        )));
+        assert!(check_version(14).is_err());
+    }
+
+    #[test]
+    fn test_read_version() {
+        assert_eq!(
+            read_version(br#"{"version": 16, "dayEntries": "anything"}"#).unwrap(),
+            16
+        );
+        assert!(read_version(br#"{"dayEntries": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_migrate_with() {
+        // a field becomes known, and another one is renamed
+        let migrations = [
+            Migration {
+                from: 15,
+                apply: |daylio| {
+                    for tag in &mut daylio.tags {
+                        tag.other
+                            .insert("isFavorite".to_owned(), Value::Bool(false));
+                    }
+                },
+            },
+            Migration {
+                from: 16,
+                apply: |daylio| {
+                    for tag in &mut daylio.tags {
+                        if let Some(favorite) = tag.other.remove("isFavorite") {
+                            tag.other.insert("favorite".to_owned(), favorite);
+                        }
+                    }
+                },
+            },
+        ];
+        let daylio = Daylio {
+            tags: vec![crate::Tag::default()],
+            ..Default::default()
+        };
+
+        let mut to_16 = daylio.clone();
+        migrate_with(&mut to_16, 16, &migrations);
+        assert_eq!((to_16.version, to_16.metadata.android_version), (16, 16));
+        assert!(to_16.tags[0].other.contains_key("isFavorite"));
+
+        let mut to_17 = daylio.clone();
+        migrate_with(&mut to_17, 17, &migrations);
+        assert_eq!(to_17.version, 17);
+        assert_eq!(
+            to_17.tags[0].other.get("favorite"),
+            Some(&Value::Bool(false))
+        );
+
+        // a step at a time gives the same result
+        migrate_with(&mut to_16, 17, &migrations);
+        assert_eq!(to_16.tags[0].other, to_17.tags[0].other);
+
+        // never downgraded
+        migrate_with(&mut to_17, 15, &migrations);
+        assert_eq!(to_17.version, 17);
+    }
+}
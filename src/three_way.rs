@@ -0,0 +1,625 @@
+//! Merges of two backups with their common ancestor. Without it, an entry deleted in one file looks
+//! the same as an entry added in the other one, and comes back.
+//!
+//! Both files are first reconciled with the ancestor: renames, deletions and edits made on one side
+//! only are applied to the other side. The usual merge then only sees what both sides changed.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::merge::{ProjectEq, second_is_newer};
+use crate::{
+    Asset, Backup, Conflict, CustomMood, DayEntry, Daylio, merge_backups, merge_with_report,
+    MergeReport, MergeStrategy, Resolution, Tag,
+};
+
+/// Fields compared between the ancestor and both files, with names and checksums instead of ids
+#[derive(Clone, Copy)]
+enum Field {
+    Mood,
+    NoteTitle,
+    Note,
+    Tags,
+    Assets,
+    TimeZoneOffset,
+    Other,
+}
+
+const FIELDS: [Field; 7] = [
+    Field::Mood,
+    Field::NoteTitle,
+    Field::Note,
+    Field::Tags,
+    Field::Assets,
+    Field::TimeZoneOffset,
+    Field::Other,
+];
+
+/// Sorted, so that the order does not matter
+fn sorted_names(names: impl Iterator<Item = String>) -> Value {
+    let mut names = names.collect::<Vec<_>>();
+    names.sort_unstable();
+    names.into()
+}
+
+impl Field {
+    fn value(self, daylio: &Daylio, entry: &DayEntry) -> Value {
+        match self {
+            Field::Mood => daylio
+                .custom_moods
+                .iter()
+                .find(|mood| mood.id == entry.mood)
+                .map(|mood| mood.name().to_lowercase())
+                .into(),
+            Field::NoteTitle => entry.note_title.clone().into(),
+            Field::Note => entry.note.clone().into(),
+            Field::Tags => sorted_names(entry.tags.iter().map(|id| {
+                daylio
+                    .tags
+                    .iter()
+                    .find(|tag| tag.id == *id)
+                    .map_or_else(String::new, ProjectEq::project)
+            })),
+            Field::Assets => sorted_names(entry.assets.iter().map(|id| {
+                daylio
+                    .assets
+                    .iter()
+                    .find(|asset| asset.id == *id)
+                    .map_or_else(String::new, ProjectEq::project)
+            })),
+            Field::TimeZoneOffset => entry.time_zone_offset.into(),
+            Field::Other => entry.other.clone().into(),
+        }
+    }
+
+    /// Copies the field of `source[from]` to `target[to]`, adding the moods, tags and assets it needs
+    fn copy(self, target: &mut Daylio, to: usize, source: &Daylio, from: usize) {
+        let entry = &source.day_entries[from];
+        match self {
+            Field::Mood => {
+                if let Some(mood) = source.custom_moods.iter().find(|m| m.id == entry.mood) {
+                    target.day_entries[to].mood = mood_id_in(target, mood);
+                }
+            }
+            Field::NoteTitle => target.day_entries[to]
+                .note_title
+                .clone_from(&entry.note_title),
+            Field::Note => target.day_entries[to].note.clone_from(&entry.note),
+            Field::Tags => {
+                let tags = source
+                    .tags
+                    .iter()
+                    .filter(|tag| entry.tags.contains(&tag.id))
+                    .map(|tag| tag_id_in(target, source, tag))
+                    .collect();
+                target.day_entries[to].tags = tags;
+            }
+            Field::Assets => {
+                let assets = source
+                    .assets
+                    .iter()
+                    .filter(|asset| entry.assets.contains(&asset.id))
+                    .map(|asset| asset_id_in(target, asset))
+                    .collect();
+                target.day_entries[to].assets = assets;
+            }
+            Field::TimeZoneOffset => {
+                target.day_entries[to].time_zone_offset = entry.time_zone_offset;
+            }
+            Field::Other => target.day_entries[to].other.clone_from(&entry.other),
+        }
+    }
+}
+
+fn next_id(ids: impl Iterator<Item = i64>) -> i64 {
+    ids.max().unwrap_or(0) + 1
+}
+
+fn mood_id_in(target: &mut Daylio, mood: &CustomMood) -> i64 {
+    if let Some(existing) = target
+        .custom_moods
+        .iter()
+        .find(|m| m.project() == mood.project())
+    {
+        return existing.id;
+    }
+    let id = next_id(target.custom_moods.iter().map(|m| m.id));
+    target.custom_moods.push(CustomMood { id, ..mood.clone() });
+    id
+}
+
+fn tag_id_in(target: &mut Daylio, source: &Daylio, tag: &Tag) -> i64 {
+    if let Some(existing) = target.tags.iter().find(|t| t.project() == tag.project()) {
+        return existing.id;
+    }
+    // the group with the same name, if any
+    let group = source
+        .tag_groups
+        .iter()
+        .find(|group| group.id == tag.id_tag_group)
+        .and_then(|group| {
+            target
+                .tag_groups
+                .iter()
+                .find(|other| other.project() == group.project())
+        })
+        .map_or(0, |group| group.id);
+    let id = next_id(target.tags.iter().map(|t| t.id));
+    target.tags.push(Tag {
+        id,
+        id_tag_group: group,
+        ..tag.clone()
+    });
+    id
+}
+
+fn asset_id_in(target: &mut Daylio, asset: &Asset) -> i64 {
+    if let Some(existing) = target.assets.iter().find(|a| a.checksum == asset.checksum) {
+        return existing.id;
+    }
+    let id = next_id(target.assets.iter().map(|a| a.id));
+    target.assets.push(Asset {
+        id,
+        ..asset.clone()
+    });
+    id
+}
+
+/// Gives the items renamed on one side the same name everywhere. The first file wins when both renamed it.
+/// Items are followed through renames by `same`, such as their creation time
+fn propagate_renames<T>(
+    base: &mut [T],
+    first: &mut [T],
+    second: &mut [T],
+    name: impl Fn(&mut T) -> &mut String,
+    same: impl Fn(&T, &T) -> bool,
+) {
+    for item in base.iter_mut() {
+        let base_name = name(item).to_lowercase();
+
+        let find = |items: &mut [T]| {
+            // predefined moods have no name
+            let by_name = items
+                .iter_mut()
+                .position(|other| !base_name.is_empty() && name(other).to_lowercase() == base_name);
+            by_name.or_else(|| items.iter().position(|other| same(item, other)))
+        };
+        let in_first = find(first);
+        let in_second = find(second);
+
+        let renamed = |items: &mut [T], index: Option<usize>| {
+            index
+                .map(|index| name(&mut items[index]).clone())
+                .filter(|other| other.to_lowercase() != base_name)
+        };
+        let Some(new_name) = renamed(first, in_first).or_else(|| renamed(second, in_second)) else {
+            continue;
+        };
+
+        name(item).clone_from(&new_name);
+        for (items, index) in [(&mut *first, in_first), (&mut *second, in_second)] {
+            if let Some(index) = index {
+                name(&mut items[index]).clone_from(&new_name);
+            }
+        }
+    }
+}
+
+/// Items created by an import have no creation time
+fn same_creation(base: i64, other: i64) -> bool {
+    base != 0 && base == other
+}
+
+/// What `reconcile` did, for the report
+#[derive(Default)]
+struct Reconciliation {
+    /// Entries of the second file folded into the ones of the first file
+    folded: Vec<i64>,
+    deleted: Vec<i64>,
+    /// Entries deleted on one side and edited on the other one
+    conflicts: Vec<Conflict>,
+}
+
+/// Indices of the entries of each file, by timestamp
+fn by_datetime(daylio: &Daylio, side: usize, indices: &mut BTreeMap<i64, [Vec<usize>; 3]>) {
+    for (i, entry) in daylio.day_entries.iter().enumerate() {
+        indices.entry(entry.datetime).or_default()[side].push(i);
+    }
+}
+
+/// Applies to each file what only the other one changed since `base`.
+/// Entries deleted on one side and edited on the other one are resolved with `strategy`
+fn reconcile(
+    base: &Daylio,
+    first: &mut Daylio,
+    second: &mut Daylio,
+    strategy: &mut MergeStrategy,
+) -> Reconciliation {
+    let second_is_newer = second_is_newer(first, second);
+    let mut base = base.clone();
+    propagate_renames(
+        &mut base.tags,
+        &mut first.tags,
+        &mut second.tags,
+        |tag| &mut tag.name,
+        |base, tag| same_creation(base.created_at, tag.created_at),
+    );
+    propagate_renames(
+        &mut base.custom_moods,
+        &mut first.custom_moods,
+        &mut second.custom_moods,
+        |mood| &mut mood.custom_name,
+        |base, mood| {
+            (base.predefined_name_id != -1 && base.predefined_name_id == mood.predefined_name_id)
+                || same_creation(base.created_at, mood.created_at)
+        },
+    );
+
+    let mut indices = BTreeMap::new();
+    by_datetime(&base, 0, &mut indices);
+    by_datetime(first, 1, &mut indices);
+    by_datetime(second, 2, &mut indices);
+
+    let mut reconciliation = Reconciliation::default();
+    let mut removed_first = vec![false; first.day_entries.len()];
+    let mut removed_second = vec![false; second.day_entries.len()];
+
+    for (datetime, [in_base, in_first, in_second]) in indices {
+        // entries recorded at the same time are paired in order, the other ones are new
+        for (k, &b) in in_base.iter().enumerate() {
+            let base_entry = &base.day_entries[b];
+            let unchanged = |daylio: &Daylio, i: usize| {
+                FIELDS.iter().all(|field| {
+                    field.value(&base, base_entry) == field.value(daylio, &daylio.day_entries[i])
+                })
+            };
+
+            match (in_first.get(k).copied(), in_second.get(k).copied()) {
+                (Some(i), Some(j)) => {
+                    let mut conflicting = false;
+                    for field in FIELDS {
+                        let value = field.value(&base, base_entry);
+                        let value1 = field.value(first, &first.day_entries[i]);
+                        let value2 = field.value(second, &second.day_entries[j]);
+                        if value1 == value2 {
+                            continue;
+                        }
+                        if value == value1 {
+                            field.copy(first, i, second, j);
+                        } else if value == value2 {
+                            field.copy(second, j, first, i);
+                        } else {
+                            // both changed it, left to the merge strategy
+                            conflicting = true;
+                        }
+                    }
+                    if !conflicting {
+                        removed_second[j] = true;
+                        reconciliation.folded.push(datetime);
+                    }
+                }
+                (Some(i), None) if unchanged(first, i) => {
+                    removed_first[i] = true;
+                    reconciliation.deleted.push(datetime);
+                }
+                (None, Some(j)) if unchanged(second, j) => {
+                    removed_second[j] = true;
+                    reconciliation.deleted.push(datetime);
+                }
+                (Some(i), None) => {
+                    let entry = Some(&first.day_entries[i]);
+                    let resolution = strategy.resolve(first, entry, None, second_is_newer);
+                    // the second file deleted it
+                    removed_first[i] = resolution == Resolution::KeepSecond;
+                    reconciliation.conflicts.push(Conflict {
+                        datetime,
+                        fields: vec!["deleted"],
+                        resolution,
+                    });
+                }
+                (None, Some(j)) => {
+                    let entry = Some(&second.day_entries[j]);
+                    let resolution = strategy.resolve(second, None, entry, second_is_newer);
+                    // the first file deleted it
+                    removed_second[j] = resolution == Resolution::KeepFirst;
+                    reconciliation.conflicts.push(Conflict {
+                        datetime,
+                        fields: vec!["deleted"],
+                        resolution,
+                    });
+                }
+                (None, None) => reconciliation.deleted.push(datetime),
+            }
+        }
+    }
+
+    let mut removed = removed_first.into_iter();
+    first
+        .day_entries
+        .retain(|_| !removed.next().unwrap_or(false));
+    let mut removed = removed_second.into_iter();
+    second
+        .day_entries
+        .retain(|_| !removed.next().unwrap_or(false));
+
+    reconciliation
+}
+
+/// Fills the report with what the reconciliation did before the merge
+fn complete_report(
+    report: &mut MergeReport,
+    reconciliation: Reconciliation,
+    first_entries: usize,
+    second_entries: usize,
+) {
+    report.first_entries = first_entries;
+    report.second_entries = second_entries;
+    report.deduplicated_entries.extend(reconciliation.folded);
+    report.deduplicated_entries.sort_unstable();
+    report.deleted_entries = reconciliation.deleted;
+    report.conflicts.extend(reconciliation.conflicts);
+    report.conflicts.sort_by_key(|conflict| conflict.datetime);
+}
+
+/// Merges two files that both come from `base`, such as backups of two devices restored from the same backup.
+///
+/// Entries are matched by their timestamp. Entries of `base` deleted in one file are deleted. Notes, moods, tags and
+/// renames of moods and tags made in one file only are kept.
+/// Entries that both files edited differently, or that one deleted and the other edited, are resolved according to
+/// `strategy`. Keeping both versions of a deleted entry keeps the edited one.
+/// Moods and tags are never deleted, and the first file wins when both renamed the same one
+#[must_use]
+pub fn merge_three_way(
+    base: &Daylio,
+    mut daylio1: Daylio,
+    mut daylio2: Daylio,
+    mut strategy: MergeStrategy,
+) -> (Daylio, MergeReport) {
+    let (first_entries, second_entries) = (daylio1.day_entries.len(), daylio2.day_entries.len());
+    let reconciliation = reconcile(base, &mut daylio1, &mut daylio2, &mut strategy);
+
+    let (merged, mut report) = merge_with_report(daylio1, daylio2, strategy);
+    complete_report(&mut report, reconciliation, first_entries, second_entries);
+    (merged, report)
+}
+
+/// Same as [`merge_three_way`], with the photos and audio files of both backups
+#[must_use]
+pub fn merge_backups_three_way(
+    base: &Daylio,
+    mut backup1: Backup,
+    mut backup2: Backup,
+    mut strategy: MergeStrategy,
+) -> (Backup, MergeReport) {
+    let first_entries = backup1.daylio.day_entries.len();
+    let second_entries = backup2.daylio.day_entries.len();
+    let reconciliation = reconcile(
+        base,
+        &mut backup1.daylio,
+        &mut backup2.daylio,
+        &mut strategy,
+    );
+
+    let (merged, mut report) = merge_backups(backup1, backup2, strategy);
+    complete_report(&mut report, reconciliation, first_entries, second_entries);
+    (merged, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(id: i64, name: &str, created_at: i64) -> Tag {
+        Tag {
+            id,
+            name: name.to_owned(),
+            created_at,
+            ..Default::default()
+        }
+    }
+
+    fn entry(datetime: i64, mood: i64, tags: Vec<i64>, note: &str) -> DayEntry {
+        DayEntry {
+            datetime,
+            mood,
+            tags,
+            note: note.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn base() -> Daylio {
+        Daylio {
+            tags: vec![tag(1, "gym", 10), tag(2, "work", 20)],
+            day_entries: vec![
+                entry(1000, 1, vec![1], "kept"),
+                entry(2000, 2, vec![], "deleted in the first file"),
+                entry(3000, 3, vec![2], "deleted in the second file"),
+                entry(4000, 4, vec![], "edited in both"),
+                entry(5000, 5, vec![], "deleted in one, edited in the other"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_three_way() {
+        let base = base();
+
+        let mut first = base.clone();
+        first.day_entries.remove(4);
+        first.day_entries.remove(1);
+        first.day_entries[0].mood = 5; // 1000
+        first.day_entries[2].note = "first edit".to_owned(); // 4000
+        first
+            .day_entries
+            .push(entry(6000, 1, vec![], "new in the first file"));
+
+        // the second device has other ids, and renamed "gym"
+        let mut second = base.clone();
+        second.tags = vec![tag(7, "sport", 10), tag(8, "work", 20)];
+        for entry in &mut second.day_entries {
+            for tag in &mut entry.tags {
+                *tag += 6;
+            }
+        }
+        second.day_entries.remove(2);
+        second.day_entries[0].note = "second edit".to_owned(); // 1000
+        second.day_entries[2].note = "second edit".to_owned(); // 4000
+        second.day_entries[3].note = "edited".to_owned(); // 5000
+
+        let (merged, report) = merge_three_way(&base, first, second, MergeStrategy::KeepBoth);
+
+        let mut entries = merged
+            .day_entries
+            .iter()
+            .map(|entry| {
+                let tags = entry
+                    .tags
+                    .iter()
+                    .map(|id| {
+                        merged
+                            .tags
+                            .iter()
+                            .find(|tag| tag.id == *id)
+                            .unwrap()
+                            .name
+                            .as_str()
+                    })
+                    .collect::<Vec<_>>();
+                (entry.datetime, entry.mood, tags, entry.note.as_str())
+            })
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(
+            entries,
+            vec![
+                // both edits are kept, and the rename
+                (1000, 5, vec!["sport"], "second edit"),
+                (4000, 4, vec![], "first edit"),
+                (4000, 4, vec![], "second edit"),
+                (5000, 5, vec![], "edited"),
+                (6000, 1, vec![], "new in the first file"),
+            ]
+        );
+        assert_eq!(
+            merged
+                .tags
+                .iter()
+                .map(|tag| tag.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["sport", "work"]
+        );
+
+        assert_eq!((report.first_entries, report.second_entries), (4, 4));
+        // both versions of 4000 are kept
+        assert_eq!(report.added_entries, vec![4000, 5000]);
+        assert_eq!(report.deduplicated_entries, vec![1000]);
+        assert_eq!(report.deleted_entries, vec![2000, 3000]);
+        assert_eq!(
+            report.conflicts,
+            vec![
+                Conflict {
+                    datetime: 4000,
+                    fields: vec!["note"],
+                    resolution: Resolution::KeepBoth,
+                },
+                Conflict {
+                    datetime: 5000,
+                    fields: vec!["deleted"],
+                    resolution: Resolution::KeepBoth,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deleted_and_edited() {
+        let base = base();
+        let mut first = base.clone();
+        first.day_entries.remove(4);
+        first.metadata.created_at = 2;
+        let mut second = base.clone();
+        second.day_entries[4].note = "edited".to_owned();
+        second.metadata.created_at = 1;
+
+        let kept = |strategy| {
+            let (merged, report) = merge_three_way(&base, first.clone(), second.clone(), strategy);
+            let kept = merged
+                .day_entries
+                .iter()
+                .any(|entry| entry.datetime == 5000);
+            (kept, report.conflicts[0].resolution)
+        };
+        assert_eq!(kept(MergeStrategy::KeepBoth), (true, Resolution::KeepBoth));
+        assert_eq!(kept(MergeStrategy::Union), (true, Resolution::Union));
+        assert_eq!(
+            kept(MergeStrategy::PreferFirst),
+            (false, Resolution::KeepFirst)
+        );
+        // the first file is the newest
+        assert_eq!(
+            kept(MergeStrategy::PreferNewest),
+            (false, Resolution::KeepFirst)
+        );
+
+        let mut asked = 0;
+        let mut ask = |_: &Daylio, first: Option<&DayEntry>, second: Option<&DayEntry>| {
+            asked += 1;
+            assert!(first.is_none());
+            assert_eq!(second.unwrap().note, "edited");
+            Resolution::KeepSecond
+        };
+        assert_eq!(
+            kept(MergeStrategy::Interactive(&mut ask)),
+            (true, Resolution::KeepSecond)
+        );
+        assert_eq!(asked, 1);
+    }
+
+    #[test]
+    fn test_rename_in_both() {
+        let base = base();
+        let mut first = base.clone();
+        first.tags[0].name = "sport".to_owned();
+        let mut second = base.clone();
+        second.tags[0].name = "fitness".to_owned();
+        second.day_entries[0].note = "edited".to_owned();
+
+        let (merged, report) = merge_three_way(&base, first, second, MergeStrategy::KeepBoth);
+        assert_eq!(
+            merged
+                .tags
+                .iter()
+                .map(|tag| tag.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["sport", "work"]
+        );
+        // the tag of the edited entry is still the same
+        assert!(report.conflicts.is_empty());
+        assert_eq!(merged.day_entries.len(), base.day_entries.len());
+    }
+
+    #[test]
+    fn test_rename_predefined_mood() {
+        let base = base();
+        let mut first = base.clone();
+        first.custom_moods[0].custom_name = "Ecstatic".to_owned();
+        let mut second = base.clone();
+        second.day_entries[0].note = "edited".to_owned();
+
+        let (merged, report) = merge_three_way(&base, first, second, MergeStrategy::KeepBoth);
+        assert_eq!(
+            merged
+                .custom_moods
+                .iter()
+                .map(CustomMood::name)
+                .collect::<Vec<_>>(),
+            vec!["Ecstatic", "good", "meh", "bad", "awful"]
+        );
+        // the mood of the edited entry is still the same
+        assert!(report.conflicts.is_empty());
+        assert_eq!(merged.day_entries.len(), base.day_entries.len());
+    }
+}
@@ -0,0 +1,247 @@
+//! Time zones of imported entries. PDF exports only print local times, while backups also store
+//! the UTC time and the offset the app used when the entry was written.
+
+use std::collections::HashMap;
+
+use chrono::{LocalResult, NaiveDate, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+use crate::{DayEntry, Daylio};
+
+/// Local time of the entry, from its fields
+fn local_time(entry: &DayEntry) -> Option<NaiveDateTime> {
+    // month is 0-indexed in Daylio
+    let date = NaiveDate::from_ymd_opt(
+        i32::try_from(entry.year).ok()?,
+        u32::try_from(entry.month + 1).ok()?,
+        u32::try_from(entry.day).ok()?,
+    )?;
+    date.and_hms_opt(
+        u32::try_from(entry.hour).ok()?,
+        u32::try_from(entry.minute).ok()?,
+        0,
+    )
+}
+
+/// Offset in milliseconds at the given local time, as in `DayEntry::time_zone_offset`.
+///
+/// When the clocks go back, the time happened twice and the first one is taken.
+/// When they go forward, the time never happened and the offset before the change is taken.
+fn offset_at(time_zone: Tz, local: NaiveDateTime) -> i64 {
+    let offset = match time_zone.offset_from_local_datetime(&local) {
+        LocalResult::Single(offset) | LocalResult::Ambiguous(offset, _) => offset,
+        // time zones do not change twice in a day
+        LocalResult::None => time_zone.offset_from_utc_datetime(&(local - TimeDelta::days(1))),
+    };
+    i64::from(offset.fix().local_minus_utc()) * 1000
+}
+
+/// Sets the UTC time and offset of entries that only have a local time, such as PDF imports
+pub fn apply_time_zone(daylio: &mut Daylio, time_zone: Tz) {
+    for entry in &mut daylio.day_entries {
+        let Some(local) = local_time(entry) else {
+            continue;
+        };
+        entry.time_zone_offset = offset_at(time_zone, local);
+        entry.datetime = local.and_utc().timestamp_millis() - entry.time_zone_offset;
+    }
+    // the order may change around daylight saving time changes
+    daylio.sanitize();
+}
+
+/// Copies the time of the `reference` entries written at the same minute with the same offset.
+/// PDF exports do not print seconds, which the app stores, so their entries would never match otherwise
+pub fn match_seconds(imported: &mut Daylio, reference: &Daylio) {
+    let minute = |entry: &DayEntry| (local_time(entry), entry.time_zone_offset);
+    let mut times = HashMap::new();
+    for entry in &reference.day_entries {
+        times.entry(minute(entry)).or_insert(entry.datetime);
+    }
+
+    for entry in &mut imported.day_entries {
+        if let Some(datetime) = times.get(&minute(entry)) {
+            entry.datetime = *datetime;
+        }
+    }
+    // sorts the entries again
+    imported.sanitize();
+}
+
+/// Finds the time zone that gives the offsets of the `reference` entries written in the date range
+/// of the `imported` ones. Returns `None` when the ranges do not overlap.
+///
+/// Ties are broken with the other entries, so that daylight saving time is right outside the range.
+/// Many time zones still share the same offsets, the first one in alphabetical order is returned.
+#[must_use]
+pub fn infer_time_zone(reference: &Daylio, imported: &Daylio) -> Option<Tz> {
+    let dates = imported.day_entries.iter().filter_map(local_time);
+    let first = dates.clone().min()?.date();
+    let last = dates.max()?.date();
+
+    let overlapping = reference
+        .day_entries
+        .iter()
+        .filter(|entry| {
+            local_time(entry).is_some_and(|local| (first..=last).contains(&local.date()))
+        })
+        .collect::<Vec<_>>();
+    if overlapping.is_empty() {
+        return None;
+    }
+
+    let matches = |time_zone: Tz, entry: &&DayEntry| {
+        let utc = chrono::DateTime::from_timestamp_millis(entry.datetime);
+        utc.is_some_and(|utc| {
+            let offset = time_zone.offset_from_utc_datetime(&utc.naive_utc());
+            i64::from(offset.fix().local_minus_utc()) * 1000 == entry.time_zone_offset
+        })
+    };
+
+    let mut best = None;
+    for time_zone in TZ_VARIANTS {
+        let overlapping_count = overlapping.iter().filter(|e| matches(time_zone, e)).count();
+        if overlapping_count == 0 {
+            continue;
+        }
+        let count = (
+            overlapping_count,
+            reference
+                .day_entries
+                .iter()
+                .filter(|e| matches(time_zone, e))
+                .count(),
+        );
+        if best.is_none_or(|(_, best)| count > best) {
+            best = Some((time_zone, count));
+        }
+    }
+    best.map(|(time_zone, _)| time_zone)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, NaiveDate, Timelike};
+
+    use super::*;
+
+    fn entry(date: NaiveDateTime, time_zone_offset: i64) -> DayEntry {
+        DayEntry {
+            minute: i64::from(date.minute()),
+            hour: i64::from(date.hour()),
+            day: i64::from(date.day()),
+            month: i64::from(date.month()) - 1,
+            year: i64::from(date.year()),
+            datetime: date.and_utc().timestamp_millis() - time_zone_offset,
+            time_zone_offset,
+            ..Default::default()
+        }
+    }
+
+    fn local(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_apply_time_zone() {
+        let mut daylio = Daylio {
+            day_entries: vec![
+                // summer time
+                entry(local(7, 14, 21, 30), 0),
+                // the clocks go back at 3:00 on October 29, 2:30 happens twice
+                entry(local(10, 29, 2, 30), 0),
+                // the clocks go forward at 2:00 on March 26, 2:30 never happens
+                entry(local(3, 26, 2, 30), 0),
+                entry(local(1, 2, 8, 0), 0),
+            ],
+            ..Default::default()
+        };
+        apply_time_zone(&mut daylio, chrono_tz::Europe::Paris);
+
+        let hour = 3_600_000;
+        let actual = daylio
+            .day_entries
+            .iter()
+            .map(|entry| (entry.hour, entry.datetime, entry.time_zone_offset))
+            .collect::<Vec<_>>();
+        let expected = vec![
+            (
+                2,
+                local(10, 29, 0, 30).and_utc().timestamp_millis(),
+                2 * hour,
+            ),
+            (
+                21,
+                local(7, 14, 19, 30).and_utc().timestamp_millis(),
+                2 * hour,
+            ),
+            (2, local(3, 26, 1, 30).and_utc().timestamp_millis(), hour),
+            (8, local(1, 2, 7, 0).and_utc().timestamp_millis(), hour),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_infer_time_zone() {
+        let hour = 3_600_000;
+        let reference = Daylio {
+            day_entries: vec![
+                entry(local(1, 2, 8, 0), -5 * hour),
+                entry(local(7, 14, 21, 30), -4 * hour),
+                entry(local(12, 30, 8, 0), -5 * hour),
+            ],
+            ..Default::default()
+        };
+        let imported = Daylio {
+            day_entries: vec![entry(local(7, 1, 9, 0), 0), entry(local(7, 31, 9, 0), 0)],
+            ..Default::default()
+        };
+
+        let time_zone = infer_time_zone(&reference, &imported).unwrap();
+        let mut summer = imported.clone();
+        apply_time_zone(&mut summer, time_zone);
+        assert!(summer
+            .day_entries
+            .iter()
+            .all(|entry| entry.time_zone_offset == -4 * hour));
+        // the entries outside the range tell the zone has daylight saving time
+        assert_eq!(offset_at(time_zone, local(1, 2, 8, 0)), -5 * hour);
+
+        let imported = Daylio {
+            day_entries: vec![entry(local(3, 1, 9, 0), 0)],
+            ..Default::default()
+        };
+        assert_eq!(infer_time_zone(&reference, &imported), None);
+    }
+
+    #[test]
+    fn test_match_seconds() {
+        let hour = 3_600_000;
+        let mut written = entry(local(7, 14, 21, 30), 2 * hour);
+        written.datetime += 42_123;
+        let reference = Daylio {
+            day_entries: vec![written.clone()],
+            ..Default::default()
+        };
+
+        let mut imported = Daylio {
+            day_entries: vec![
+                entry(local(7, 14, 21, 30), 2 * hour),
+                // another time zone
+                entry(local(7, 14, 21, 30), hour),
+            ],
+            ..Default::default()
+        };
+        match_seconds(&mut imported, &reference);
+
+        let actual = imported
+            .day_entries
+            .iter()
+            .map(|entry| entry.datetime)
+            .collect::<Vec<_>>();
+        let expected = vec![entry(local(7, 14, 21, 30), hour).datetime, written.datetime];
+        assert_eq!(actual, expected);
+    }
+}
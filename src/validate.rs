@@ -0,0 +1,416 @@
+//! Checks of the references and counts of a backup, that the app relies on when restoring it
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+use crate::{DayEntry, Daylio};
+
+/// Collections whose items are referenced by their id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    Mood,
+    Tag,
+    TagGroup,
+    Entry,
+    Asset,
+    Goal,
+}
+
+impl Display for IdKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IdKind::Mood => "mood",
+            IdKind::Tag => "tag",
+            IdKind::TagGroup => "tag group",
+            IdKind::Entry => "entry",
+            IdKind::Asset => "asset",
+            IdKind::Goal => "goal",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Something the app may refuse when restoring the backup. Entries are given by their id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    UnknownMood {
+        entry: i64,
+        mood: i64,
+    },
+    UnknownTag {
+        entry: i64,
+        tag: i64,
+    },
+    UnknownTagGroup {
+        tag: i64,
+        tag_group: i64,
+    },
+    /// Several items of the same collection have the same id
+    DuplicateId {
+        kind: IdKind,
+        id: i64,
+    },
+    /// The local time of the entry fields is not the one of `datetime` and `time_zone_offset`.
+    /// When the offset is 0, which older versions of the app wrote, the fields are not in any time zone
+    WrongLocalTime {
+        entry: i64,
+        fields: String,
+        expected: String,
+    },
+    /// `metadata.number_of_entries` is not the number of entries
+    EntryCountDrift {
+        expected: i64,
+        found: usize,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::UnknownMood { entry, mood } => {
+                write!(f, "Entry {entry}: mood {mood} does not exist")
+            }
+            Problem::UnknownTag { entry, tag } => {
+                write!(f, "Entry {entry}: tag {tag} does not exist")
+            }
+            Problem::UnknownTagGroup { tag, tag_group } => {
+                write!(f, "Tag {tag}: tag group {tag_group} does not exist")
+            }
+            Problem::DuplicateId { kind, id } => write!(f, "Several items with {kind} id {id}"),
+            Problem::WrongLocalTime {
+                entry,
+                fields,
+                expected,
+            } => write!(
+                f,
+                "Entry {entry}: written at {fields}, but its timestamp and offset give {expected}"
+            ),
+            Problem::EntryCountDrift { expected, found } => write!(
+                f,
+                "The metadata counts {expected} entries, the backup has {found}"
+            ),
+        }
+    }
+}
+
+/// Local time of the entry fields, formatted as [`LOCAL_TIME_FORMAT`]. Invalid fields are kept as they are
+pub(crate) fn fields_time(entry: &DayEntry) -> String {
+    // month is 0-indexed in Daylio
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        entry.year,
+        entry.month + 1,
+        entry.day,
+        entry.hour,
+        entry.minute
+    )
+}
+
+pub(crate) const LOCAL_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Offsets of the time zones in minutes, from UTC-12 to UTC+14. They are all multiples of 15 minutes
+const ZONE_OFFSETS: RangeInclusive<i64> = -12 * 60..=14 * 60;
+
+/// The local time of the entry fields, and the one given by `datetime` and `time_zone_offset`, when they differ.
+/// Older versions of the app did not record the offset and wrote 0, the fields of those entries only have to be in
+/// some time zone
+pub(crate) fn calendar_mismatch(entry: &DayEntry) -> Option<(String, NaiveDateTime)> {
+    let local =
+        DateTime::from_timestamp_millis(entry.datetime + entry.time_zone_offset)?.naive_utc();
+    let fields = fields_time(entry);
+    if fields == local.format(LOCAL_TIME_FORMAT).to_string() {
+        return None;
+    }
+    if entry.time_zone_offset == 0 && in_some_time_zone(entry) == Some(true) {
+        return None;
+    }
+    Some((fields, local))
+}
+
+/// Whether the fields are the local time of `datetime` in some time zone, for the entries without an offset
+fn in_some_time_zone(entry: &DayEntry) -> Option<bool> {
+    // in minutes
+    let utc = entry.datetime.div_euclid(60_000);
+    // month is 0-indexed in Daylio
+    let date = NaiveDate::from_ymd_opt(
+        i32::try_from(entry.year).ok()?,
+        u32::try_from(entry.month + 1).ok()?,
+        u32::try_from(entry.day).ok()?,
+    )?;
+
+    // the oldest versions of the app did not record the time either, and wrote -1
+    if entry.hour == -1 && entry.minute == -1 {
+        let local_date = |offset: i64| {
+            DateTime::from_timestamp((utc + offset) * 60, 0).map(|local| local.date_naive())
+        };
+        let earliest = local_date(*ZONE_OFFSETS.start())?;
+        let latest = local_date(*ZONE_OFFSETS.end())?;
+        return Some((earliest..=latest).contains(&date));
+    }
+
+    let written = date.and_hms_opt(
+        u32::try_from(entry.hour).ok()?,
+        u32::try_from(entry.minute).ok()?,
+        0,
+    )?;
+    let offset = written.and_utc().timestamp() / 60 - utc;
+    Some(offset % 15 == 0 && ZONE_OFFSETS.contains(&offset))
+}
+
+fn duplicate_ids(kind: IdKind, ids: impl Iterator<Item = i64>, problems: &mut Vec<Problem>) {
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    for id in ids {
+        if !seen.insert(id) && reported.insert(id) {
+            problems.push(Problem::DuplicateId { kind, id });
+        }
+    }
+}
+
+impl Daylio {
+    /// Lists the broken references, duplicated ids and inconsistent fields, without changing anything
+    #[must_use]
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        duplicate_ids(
+            IdKind::Mood,
+            self.custom_moods.iter().map(|mood| mood.id),
+            &mut problems,
+        );
+        duplicate_ids(
+            IdKind::Tag,
+            self.tags.iter().map(|tag| tag.id),
+            &mut problems,
+        );
+        duplicate_ids(
+            IdKind::TagGroup,
+            self.tag_groups.iter().map(|group| group.id),
+            &mut problems,
+        );
+        duplicate_ids(
+            IdKind::Entry,
+            self.day_entries.iter().map(|entry| entry.id),
+            &mut problems,
+        );
+        duplicate_ids(
+            IdKind::Asset,
+            self.assets.iter().map(|asset| asset.id),
+            &mut problems,
+        );
+        duplicate_ids(
+            IdKind::Goal,
+            self.goals.typed().into_iter().flatten().map(|goal| goal.id),
+            &mut problems,
+        );
+
+        let moods = self
+            .custom_moods
+            .iter()
+            .map(|mood| mood.id)
+            .collect::<HashSet<_>>();
+        let tags = self.tags.iter().map(|tag| tag.id).collect::<HashSet<_>>();
+        let tag_groups = self
+            .tag_groups
+            .iter()
+            .map(|group| group.id)
+            .collect::<HashSet<_>>();
+
+        for tag in &self.tags {
+            // 0 is used by the app for the tags without a group
+            if tag.id_tag_group != 0 && !tag_groups.contains(&tag.id_tag_group) {
+                problems.push(Problem::UnknownTagGroup {
+                    tag: tag.id,
+                    tag_group: tag.id_tag_group,
+                });
+            }
+        }
+
+        for entry in &self.day_entries {
+            if !moods.contains(&entry.mood) {
+                problems.push(Problem::UnknownMood {
+                    entry: entry.id,
+                    mood: entry.mood,
+                });
+            }
+            for tag in &entry.tags {
+                if !tags.contains(tag) {
+                    problems.push(Problem::UnknownTag {
+                        entry: entry.id,
+                        tag: *tag,
+                    });
+                }
+            }
+
+            if let Some((fields, expected)) = calendar_mismatch(entry) {
+                problems.push(Problem::WrongLocalTime {
+                    entry: entry.id,
+                    fields,
+                    expected: expected.format(LOCAL_TIME_FORMAT).to_string(),
+                });
+            }
+        }
+
+        if usize::try_from(self.metadata.number_of_entries).ok() != Some(self.day_entries.len()) {
+            problems.push(Problem::EntryCountDrift {
+                expected: self.metadata.number_of_entries,
+                found: self.day_entries.len(),
+            });
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Tag, TagGroup};
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let hour = 3_600_000;
+        // 2023-01-24 08:30 in UTC+1
+        let datetime = 1_674_545_400_000;
+        let entry = DayEntry {
+            id: 1,
+            minute: 30,
+            hour: 8,
+            day: 24,
+            month: 0,
+            year: 2023,
+            datetime,
+            time_zone_offset: hour,
+            mood: 1,
+            tags: vec![1],
+            ..Default::default()
+        };
+        let tag = Tag {
+            id: 1,
+            ..Default::default()
+        };
+        let mut daylio = Daylio {
+            tags: vec![tag.clone()],
+            day_entries: vec![entry.clone()],
+            ..Default::default()
+        };
+        daylio.metadata.number_of_entries = 1;
+        assert_eq!(daylio.validate(), vec![]);
+
+        daylio.tags.push(Tag {
+            id_tag_group: 7,
+            ..tag
+        });
+        daylio.tag_groups.push(TagGroup {
+            id: 1,
+            ..Default::default()
+        });
+        daylio.day_entries.push(DayEntry {
+            mood: 9,
+            tags: vec![1, 3],
+            time_zone_offset: 2 * hour,
+            ..entry
+        });
+
+        let expected = vec![
+            Problem::DuplicateId {
+                kind: IdKind::Tag,
+                id: 1,
+            },
+            Problem::DuplicateId {
+                kind: IdKind::TagGroup,
+                id: 1,
+            },
+            Problem::DuplicateId {
+                kind: IdKind::Entry,
+                id: 1,
+            },
+            Problem::UnknownTagGroup {
+                tag: 1,
+                tag_group: 7,
+            },
+            Problem::UnknownMood { entry: 1, mood: 9 },
+            Problem::UnknownTag { entry: 1, tag: 3 },
+            Problem::WrongLocalTime {
+                entry: 1,
+                fields: "2023-01-24 08:30".to_owned(),
+                expected: "2023-01-24 09:30".to_owned(),
+            },
+            Problem::EntryCountDrift {
+                expected: 1,
+                found: 2,
+            },
+        ];
+        assert_eq!(daylio.validate(), expected);
+        assert_eq!(
+            expected[6].to_string(),
+            "Entry 1: written at 2023-01-24 08:30, but its timestamp and offset give 2023-01-24 09:30"
+        );
+    }
+
+    #[test]
+    fn test_calendar_mismatch_without_offset() {
+        // 2018-01-26 22:59:59 UTC, written at 23:59 in UTC+1 by an app that did not record offsets
+        let entry = DayEntry {
+            minute: 59,
+            hour: 23,
+            day: 26,
+            month: 0,
+            year: 2018,
+            datetime: 1_517_007_599_716,
+            ..Default::default()
+        };
+        assert_eq!(calendar_mismatch(&entry), None);
+        // UTC+5:45
+        let nepal = DayEntry {
+            minute: 44,
+            hour: 4,
+            day: 27,
+            ..entry.clone()
+        };
+        assert_eq!(calendar_mismatch(&nepal), None);
+        let no_time = DayEntry {
+            hour: -1,
+            minute: -1,
+            day: 27,
+            ..entry.clone()
+        };
+        assert_eq!(calendar_mismatch(&no_time), None);
+
+        let utc =
+            |time: &str| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.3f").unwrap();
+        for (wrong, fields) in [
+            (
+                DayEntry {
+                    day: 25,
+                    ..entry.clone()
+                },
+                "2018-01-25 23:59",
+            ),
+            (
+                DayEntry {
+                    minute: 10,
+                    ..entry.clone()
+                },
+                "2018-01-26 23:10",
+            ),
+            (DayEntry { day: 28, ..no_time }, "2018-01-28 -1:-1"),
+            (
+                DayEntry {
+                    month: 12,
+                    ..entry.clone()
+                },
+                "2018-13-26 23:59",
+            ),
+        ] {
+            assert_eq!(
+                calendar_mismatch(&wrong),
+                Some((fields.to_owned(), utc("2018-01-26 22:59:59.716")))
+            );
+        }
+    }
+}
@@ -4,7 +4,9 @@ mod tests {
     use similar_asserts::assert_eq;
 
     use daylio_tools::{
-        CustomMood, DayEntry, Daylio, load_daylio_backup, load_daylio_pdf, Metadata, Tag,
+        CustomMood, DayEntry, Daylio, import_pdf_text, load_daylio_backup, load_daylio_csv,
+        load_daylio_pdf_text, Metadata, PdfLocale, PdfOptions, store_daylio_csv, Tag,
+        TitleHeuristic,
     };
 
     #[test]
@@ -20,10 +22,79 @@ mod tests {
     }
 
     #[test]
+    /// The CSV has no ids, compare what it does contain
+    fn csv_format() -> Result<()> {
+        let actual = load_daylio_csv("tests/data/synthetic/english.csv".as_ref())?;
+        let expected = load_daylio_backup("tests/data/official/english.daylio".as_ref())?;
+
+        assert_eq!(summarize(&actual), summarize(&expected));
+
+        Ok(())
+    }
+
+    #[test]
+    /// The file was written by `store_daylio_csv`, see `tests/data/synthetic/README.md`
+    fn csv_round_trip() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("daylio_tools_{}.csv", std::process::id()));
+
+        let daylio = load_daylio_csv("tests/data/synthetic/english.csv".as_ref())?;
+        store_daylio_csv(&daylio, &path)?;
+
+        let actual = std::fs::read_to_string(&path)?;
+        let expected = std::fs::read_to_string("tests/data/synthetic/english.csv")?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    fn summarize(daylio: &Daylio) -> Vec<String> {
+        let mut summary = daylio
+            .day_entries
+            .iter()
+            .map(|entry| {
+                let mood = daylio
+                    .custom_moods
+                    .iter()
+                    .find(|mood| mood.id == entry.mood)
+                    .map(|mood| {
+                        if mood.predefined_name_id == -1 {
+                            mood.custom_name.clone()
+                        } else {
+                            format!("predefined {}", mood.predefined_name_id)
+                        }
+                    });
+                let mut tags = entry
+                    .tags
+                    .iter()
+                    .filter_map(|id| daylio.tags.iter().find(|tag| tag.id == *id))
+                    .map(|tag| tag.name.as_str())
+                    .collect::<Vec<_>>();
+                tags.sort_unstable();
+
+                format!(
+                    "{}-{}-{} {}:{} {mood:?} {tags:?} {:?} {:?}",
+                    entry.year,
+                    entry.month,
+                    entry.day,
+                    entry.hour,
+                    entry.minute,
+                    entry.note_title,
+                    entry.note
+                )
+            })
+            .collect::<Vec<_>>();
+        summary.sort();
+        summary
+    }
+
+    #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
     /// This test shows information lost when converting from PDF to JSON.
     /// This is not so bad! The PDF format is not meant to be machine-readable.
     fn pdf_format_english() -> Result<()> {
-        let actual = load_daylio_pdf("tests/data/official/english.pdf".as_ref())?;
+        let actual = daylio_tools::load_daylio_pdf("tests/data/official/english.pdf".as_ref())?;
         let expected = expected_pdf();
 
         assert_eq!(actual, expected);
@@ -32,8 +103,9 @@ mod tests {
     }
 
     #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
     fn pdf_format_french() -> Result<()> {
-        let actual = load_daylio_pdf("tests/data/official/french.pdf".as_ref())?;
+        let actual = daylio_tools::load_daylio_pdf("tests/data/official/french.pdf".as_ref())?;
         let expected = expected_pdf();
 
         assert_eq!(actual, expected);
@@ -41,6 +113,161 @@ mod tests {
         Ok(())
     }
 
+    /// Hand-written text of `english.pdf` in the experimental languages, see `tests/data/synthetic/README.md`
+    const SYNTHETIC_LANGUAGES: [(&str, &str); 7] = [
+        ("de", "german"),
+        ("es", "spanish"),
+        ("it", "italian"),
+        ("pt", "portuguese"),
+        ("nl", "dutch"),
+        ("pl", "polish"),
+        ("ru", "russian"),
+    ];
+
+    /// Experimental languages are not detected
+    fn synthetic_options(code: &str) -> PdfOptions {
+        PdfOptions {
+            locale: PdfLocale::from_code(code),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pdf_format_synthetic() -> Result<()> {
+        for (code, language) in SYNTHETIC_LANGUAGES {
+            let path = format!("tests/data/synthetic/{language}.txt");
+            let actual = load_daylio_pdf_text(path.as_ref(), &synthetic_options(code))?;
+            let expected = expected_pdf();
+
+            assert_eq!(actual, expected, "{language}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    /// The statistics page of the exports counts every imported entry and tag
+    fn pdf_verification() -> Result<()> {
+        for (code, language) in SYNTHETIC_LANGUAGES {
+            let path = format!("tests/data/synthetic/{language}.txt");
+            let import = import_pdf_text(path.as_ref(), &synthetic_options(code))?;
+            assert!(
+                import.verification.is_complete(),
+                "{language}: {}",
+                import.verification
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
+    fn pdf_verification_official() -> Result<()> {
+        for path in [
+            "tests/data/official/english.pdf",
+            "tests/data/official/french.pdf",
+        ] {
+            let import = daylio_tools::import_pdf(path.as_ref(), &PdfOptions::default())?;
+            assert!(
+                import.verification.is_complete(),
+                "{path}: {}",
+                import.verification
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn pdf_time_zone() -> Result<()> {
+        let options = PdfOptions {
+            time_zone: Some("Europe/Berlin".parse()?),
+            ..synthetic_options("de")
+        };
+        let actual = import_pdf_text("tests/data/synthetic/german.txt".as_ref(), &options)?.daylio;
+        let expected = load_daylio_backup("tests/data/official/english.daylio".as_ref())?;
+
+        // the backup was written in the same time zone, but has seconds
+        let times = |daylio: &Daylio| {
+            let times = daylio.day_entries.iter();
+            times
+                .map(|entry| (entry.datetime / 60_000, entry.time_zone_offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(times(&actual), times(&expected));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(feature = "poppler", feature = "rust-pdf"))]
+    /// The entries of a PDF get the times of the same entries in a backup
+    fn pdf_inferred_time_zone() -> Result<()> {
+        let backup = load_daylio_backup("tests/data/official/english.daylio".as_ref())?;
+        let path = "tests/data/official/english.pdf";
+        let mut actual = daylio_tools::import_pdf(path.as_ref(), &PdfOptions::default())?.daylio;
+
+        let time_zone = daylio_tools::infer_time_zone(&backup, &actual).unwrap();
+        daylio_tools::apply_time_zone(&mut actual, time_zone);
+        daylio_tools::match_seconds(&mut actual, &backup);
+
+        let times = |daylio: &Daylio| {
+            let times = daylio.day_entries.iter();
+            times
+                .map(|entry| (entry.datetime, entry.time_zone_offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(times(&actual), times(&backup));
+
+        Ok(())
+    }
+
+    #[test]
+    /// The entries of a CSV get the times of the same entries in a backup, so merging them adds nothing
+    fn csv_inferred_time_zone() -> Result<()> {
+        let backup = load_daylio_backup("tests/data/official/english.daylio".as_ref())?;
+        let mut csv = load_daylio_csv("tests/data/synthetic/english.csv".as_ref())?;
+
+        let time_zone = daylio_tools::infer_time_zone(&backup, &csv).unwrap();
+        daylio_tools::apply_time_zone(&mut csv, time_zone);
+        daylio_tools::match_seconds(&mut csv, &backup);
+
+        let (merged, report) = daylio_tools::merge_backups(
+            backup.clone().into(),
+            csv.into(),
+            daylio_tools::MergeStrategy::KeepBoth,
+        );
+        // only the conflicting entries are kept twice.
+        // The CSV does not tell the group of custom moods, the guessed one makes it another mood
+        assert_eq!(
+            merged.daylio.day_entries.len(),
+            backup.day_entries.len() + report.conflicts.len()
+        );
+        let fields = report
+            .conflicts
+            .iter()
+            .map(|conflict| conflict.fields.clone());
+        assert_eq!(fields.collect::<Vec<_>>(), [["mood"]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pdf_note_titles() -> Result<()> {
+        let options = PdfOptions {
+            titles: TitleHeuristic::Guess,
+            ..synthetic_options("de")
+        };
+        let actual = load_daylio_pdf_text("tests/data/synthetic/german.txt".as_ref(), &options)?;
+
+        let entry = &actual.day_entries[1];
+        assert_eq!(entry.note_title, "Note title");
+        assert_eq!(entry.note, "Note body");
+
+        Ok(())
+    }
+
     fn expected_pdf() -> Daylio {
         let mut expected_moods = Daylio::default().custom_moods;
         // Unfortunately, the PDF format does not contain the mood group id, so it is guessed
@@ -56,6 +283,7 @@ mod tests {
                 predefined_name_id: -1,
                 state: 0,
                 created_at: 0,
+                ..Default::default()
             },
         );
 
@@ -71,6 +299,7 @@ mod tests {
                     order: 1,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 2,
@@ -80,6 +309,7 @@ mod tests {
                     order: 2,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 3,
@@ -89,6 +319,7 @@ mod tests {
                     order: 3,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 4,
@@ -98,6 +329,7 @@ mod tests {
                     order: 4,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 5,
@@ -107,6 +339,7 @@ mod tests {
                     order: 5,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 6,
@@ -116,6 +349,7 @@ mod tests {
                     order: 6,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 7,
@@ -125,6 +359,7 @@ mod tests {
                     order: 7,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
                 Tag {
                     id: 8,
@@ -134,6 +369,7 @@ mod tests {
                     order: 8,
                     state: 0,
                     id_tag_group: 0,
+                    ..Default::default()
                 },
             ],
             day_entries: vec![
@@ -151,6 +387,7 @@ mod tests {
                     note_title: String::new(),
                     tags: vec![],
                     assets: vec![],
+                    ..Default::default()
                 },
                 DayEntry {
                     id: 2,
@@ -172,6 +409,7 @@ mod tests {
                         6,
                     ],
                     assets: vec![],
+                    ..Default::default()
                 },
                 DayEntry {
                     id: 3,
@@ -189,6 +427,7 @@ mod tests {
                         4,
                     ],
                     assets: vec![],
+                    ..Default::default()
                 },
                 DayEntry {
                     id: 4,
@@ -206,6 +445,7 @@ mod tests {
                         4, 5, 3, 7,
                     ],
                     assets: vec![],
+                    ..Default::default()
                 },
                 DayEntry {
                     id: 5,
@@ -222,6 +462,7 @@ mod tests {
                     note_title: String::new(),
                     tags: vec![],
                     assets: vec![],
+                    ..Default::default()
                 },
             ],
             metadata: Metadata {
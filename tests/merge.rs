@@ -2,7 +2,12 @@
 mod tests {
     use color_eyre::Result;
 
-    use daylio_tools::{CustomMood, DayEntry, Daylio, load_daylio_backup, merge, Tag};
+    use daylio_tools::{
+        Asset, AssetFile, Backup, CustomMood, DayEntry, Daylio, diff, EntryChange, Goal, GoalEntry,
+        load_daylio_backup, load_daylio_json, merge, merge_backups, merge_with_report,
+        merge_three_way, merge_with_strategy, IdMapping, MergeStrategy, NameChange, Resolution,
+        store_daylio_json, Tag, TagGroup,
+    };
 
     fn base_input() -> Daylio {
         Daylio {
@@ -17,6 +22,7 @@ mod tests {
                     predefined_name_id: 1,
                     state: 0,
                     created_at: 1651129353725,
+                    ..Default::default()
                 },
                 CustomMood {
                     id: 2,
@@ -27,6 +33,7 @@ mod tests {
                     predefined_name_id: 2,
                     state: 0,
                     created_at: 1651129353725,
+                    ..Default::default()
                 },
                 CustomMood {
                     id: 3,
@@ -37,6 +44,7 @@ mod tests {
                     predefined_name_id: 3,
                     state: 0,
                     created_at: 1651129353725,
+                    ..Default::default()
                 },
                 CustomMood {
                     id: 4,
@@ -47,6 +55,7 @@ mod tests {
                     predefined_name_id: 4,
                     state: 0,
                     created_at: 1651129353725,
+                    ..Default::default()
                 },
                 CustomMood {
                     id: 5,
@@ -57,6 +66,7 @@ mod tests {
                     predefined_name_id: 5,
                     state: 0,
                     created_at: 1651129353725,
+                    ..Default::default()
                 },
             ],
             ..Daylio::default()
@@ -76,6 +86,7 @@ mod tests {
                 predefined_name_id: -1,
                 state: 0,
                 created_at: 1651129353725,
+                ..Default::default()
             },
         );
 
@@ -88,6 +99,7 @@ mod tests {
                 order: 1,
                 state: 0,
                 id_tag_group: 1,
+                ..Default::default()
             },
             Tag {
                 id: 28,
@@ -97,6 +109,7 @@ mod tests {
                 order: 5,
                 state: 0,
                 id_tag_group: 2,
+                ..Default::default()
             },
         ];
 
@@ -115,6 +128,7 @@ mod tests {
                 note_title: "".to_owned(),
                 tags: vec![24],
                 assets: vec![],
+                ..Default::default()
             },
             DayEntry {
                 id: 2,
@@ -130,6 +144,7 @@ mod tests {
                 note_title: "".to_owned(),
                 tags: vec![28],
                 assets: vec![],
+                ..Default::default()
             },
             DayEntry {
                 id: 3,
@@ -145,6 +160,7 @@ mod tests {
                 note_title: "".to_owned(),
                 tags: vec![24, 28],
                 assets: vec![],
+                ..Default::default()
             },
         ];
         input.metadata.number_of_entries = 3;
@@ -168,6 +184,7 @@ mod tests {
                 order: 1,
                 state: 0,
                 id_tag_group: 1,
+                ..Default::default()
             },
             Tag {
                 id: 2,
@@ -177,6 +194,7 @@ mod tests {
                 order: 2,
                 state: 0,
                 id_tag_group: 2,
+                ..Default::default()
             },
         ];
 
@@ -212,4 +230,523 @@ mod tests {
 
         Ok(())
     }
+
+    fn photo(checksum: &str, data: &[u8]) -> AssetFile {
+        AssetFile {
+            path: format!("assets/photos/2022/8/{checksum}"),
+            data: data.to_vec(),
+        }
+    }
+
+    fn backup_with_photos(first_entry: usize, checksums: &[&str]) -> Backup {
+        let mut daylio = input1();
+        daylio.assets = checksums
+            .iter()
+            .enumerate()
+            .map(|(i, checksum)| Asset {
+                id: i as i64 + 1,
+                checksum: (*checksum).to_owned(),
+                asset_type: 1,
+                created_at: 1659463200000 + i as i64,
+                ..Default::default()
+            })
+            .collect();
+        daylio.day_entries[first_entry].assets = (1..=checksums.len() as i64).collect();
+
+        Backup {
+            assets: checksums
+                .iter()
+                .map(|checksum| photo(checksum, checksum.as_bytes()))
+                .collect(),
+            daylio,
+        }
+    }
+
+    #[test]
+    /// Ids are renumbered and references updated, nothing the app would refuse
+    fn merged_backup_is_valid() -> Result<()> {
+        let older = load_daylio_backup("tests/data/old.daylio".as_ref())?;
+        let newer = load_daylio_json("tests/data/newer_version.json".as_ref())?;
+        assert_eq!(older.validate(), vec![]);
+        assert_eq!(newer.validate(), vec![]);
+
+        assert_eq!(merge(older, newer).validate(), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    /// A merge only adds to the first file, whatever the new ids
+    fn diff_of_a_merge() -> Result<()> {
+        let older = load_daylio_backup("tests/data/old.daylio".as_ref())?;
+        let newer = load_daylio_json("tests/data/newer_version.json".as_ref())?;
+        let merged = merge(older.clone(), newer.clone());
+
+        let changes = diff(&older, &merged);
+        assert!(changes
+            .tags
+            .iter()
+            .all(|change| matches!(change, NameChange::Added(_))));
+        let added = changes
+            .entries
+            .iter()
+            .filter(|change| matches!(change, EntryChange::Added { .. }))
+            .count();
+        assert_eq!(added, newer.day_entries.len());
+        assert_eq!(added, changes.entries.len());
+
+        assert!(diff(&merged, &merged).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    /// Two copies of the same backup, one with deleted entries and the other with an edited note
+    fn three_way_merge_of_copies() -> Result<()> {
+        let base = load_daylio_backup("tests/data/old.daylio".as_ref())?;
+        let mut phone = base.clone();
+        let deleted = phone.day_entries.drain(10..13).collect::<Vec<_>>();
+        let mut tablet = base.clone();
+        tablet.day_entries[20].note = "edited on the tablet".to_owned();
+
+        let (merged, report) =
+            merge_three_way(&base, phone, tablet.clone(), MergeStrategy::KeepBoth);
+        assert_eq!(merged.day_entries.len(), base.day_entries.len() - 3);
+        assert_eq!(report.deleted_entries.len(), 3);
+        assert!(report.added_entries.is_empty());
+        assert!(report.conflicts.is_empty());
+        assert!(merged
+            .day_entries
+            .iter()
+            .all(|entry| deleted.iter().all(|gone| gone.datetime != entry.datetime)));
+        assert!(merged
+            .day_entries
+            .iter()
+            .any(|entry| entry.note == "edited on the tablet"));
+        assert_eq!(merged.validate(), vec![]);
+
+        // without the base, deleted entries come back and the edit is a conflict
+        let (merged, report) = merge_with_report(base.clone(), tablet, MergeStrategy::KeepBoth);
+        assert_eq!(merged.day_entries.len(), base.day_entries.len() + 1);
+        assert_eq!(report.conflicts.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_keeps_unknown_fields() -> Result<()> {
+        let newer = load_daylio_json("tests/data/newer_version.json".as_ref())?;
+        let older = load_daylio_backup("tests/data/old.daylio".as_ref())?;
+        let older_version = older.version;
+
+        for merged in [
+            merge(older.clone(), newer.clone()),
+            merge(newer.clone(), older),
+        ] {
+            // written in the schema of the newest file
+            assert_eq!(merged.version, newer.version.max(older_version));
+            assert_eq!(merged.other, newer.other);
+            let entry = merged
+                .day_entries
+                .iter()
+                .find(|entry| entry.datetime == newer.day_entries[0].datetime)
+                .unwrap();
+            assert_eq!(entry.other, newer.day_entries[0].other);
+            let tag = merged
+                .tags
+                .iter()
+                .find(|tag| tag.name == newer.tags[0].name);
+            assert_eq!(tag.unwrap().other, newer.tags[0].other);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_assets() {
+        let backup1 = backup_with_photos(0, &["aaaa", "bbbb"]);
+        let mut backup2 = backup_with_photos(1, &["bbbb", "cccc"]);
+        // a file that is not referenced by any asset is dropped
+        backup2.assets.push(photo("dddd", b"dddd"));
+
+        let (merged, _) = merge_backups(backup1, backup2, MergeStrategy::KeepBoth);
+
+        let checksums = merged
+            .daylio
+            .assets
+            .iter()
+            .map(|asset| asset.checksum.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(checksums, ["aaaa", "bbbb", "cccc"]);
+
+        let files = merged
+            .assets
+            .iter()
+            .map(AssetFile::checksum)
+            .collect::<Vec<_>>();
+        assert_eq!(files, ["aaaa", "bbbb", "cccc"]);
+
+        // entries are sorted from the most recent
+        let entry_assets = merged
+            .daylio
+            .day_entries
+            .iter()
+            .filter(|entry| !entry.assets.is_empty())
+            .map(|entry| {
+                entry
+                    .assets
+                    .iter()
+                    .map(|id| {
+                        let asset = merged.daylio.assets.iter().find(|a| a.id == *id).unwrap();
+                        asset.checksum.as_str()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entry_assets, [["aaaa", "bbbb"], ["bbbb", "cccc"]]);
+
+        assert_eq!(merged.daylio.metadata.number_of_photos, 3);
+        assert_eq!(merged.daylio.metadata.photos_size, 12);
+    }
+
+    #[test]
+    /// Without the files, shared photos are not counted twice in the size
+    fn merge_photo_metadata() {
+        let mut backup1 = backup_with_photos(0, &["aaaa", "bbbb"]);
+        let mut backup2 = backup_with_photos(1, &["bbbb", "cccc"]);
+        backup1.update_metadata();
+        backup2.update_metadata();
+
+        let merged = merge(backup1.daylio, backup2.daylio);
+        assert_eq!(merged.metadata.number_of_photos, 3);
+        assert_eq!(merged.metadata.photos_size, 12);
+    }
+
+    fn goal(id: i64, name: &str, id_tag: Option<i64>, created_at: i64) -> Goal {
+        Goal {
+            id,
+            name: name.to_owned(),
+            id_icon: Some(3),
+            id_tag,
+            created_at: Some(created_at),
+            order: Some(id),
+            ..Default::default()
+        }
+    }
+
+    fn check_in(id: i64, goal_id: i64, day: i64) -> GoalEntry {
+        GoalEntry {
+            id,
+            goal_id,
+            minute: 0,
+            hour: 20,
+            day,
+            month: 7,
+            year: 2022,
+            created_at: Some(1659463200000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_goals() {
+        let mut input1 = input1();
+        input1.goals = vec![goal(1, "Exercise", Some(28), 1)].into();
+        input1.goal_entries = vec![check_in(1, 1, 1), check_in(2, 1, 2)].into();
+
+        let mut input2 = input1.clone();
+        // same goal, created on another device
+        input2.goals = vec![goal(4, "Read", None, 2), goal(7, "exercise", Some(28), 3)].into();
+        input2.goal_entries = vec![check_in(1, 7, 2), check_in(2, 7, 3), check_in(3, 4, 3)].into();
+
+        let merged = merge(input1, input2);
+
+        let goals = merged
+            .goals
+            .typed()
+            .unwrap()
+            .iter()
+            .map(|goal| (goal.id, goal.name.as_str(), goal.id_tag, goal.order))
+            .collect::<Vec<_>>();
+        // tag2 has been renumbered to 2 by the merge
+        assert_eq!(
+            goals,
+            [
+                (1, "Exercise", Some(2), Some(1)),
+                (2, "Read", None, Some(2))
+            ]
+        );
+
+        let check_ins = merged
+            .goal_entries
+            .typed()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.id, entry.goal_id, entry.day))
+            .collect::<Vec<_>>();
+        assert_eq!(check_ins, [(1, 1, 1), (2, 1, 2), (3, 1, 3), (4, 2, 3)]);
+    }
+
+    #[test]
+    /// Check-ins refer to the `goal_id` of their goal, which is not its `id`
+    fn merge_goals_round_trip() -> Result<()> {
+        let phone = load_daylio_json("tests/data/synthetic/goals.json".as_ref())?;
+        let mut tablet = phone.clone();
+        // the same goals, listed in another order with other ids, and one more check-in
+        let goals = tablet.goals.typed_mut().unwrap();
+        goals.reverse();
+        goals[0].id = 1;
+        goals[1].id = 2;
+        let check_ins = tablet.goal_entries.typed_mut().unwrap();
+        let mut check_in = check_ins[0].clone();
+        check_in.day = 3;
+        check_ins.push(check_in);
+
+        let path =
+            std::env::temp_dir().join(format!("daylio_tools_goals_{}.json", std::process::id()));
+        store_daylio_json(&merge(phone, tablet), &path)?;
+        let merged = load_daylio_json(&path)?;
+        std::fs::remove_file(&path)?;
+
+        let goal_name = |goal_id| {
+            let goals = merged.goals.typed().unwrap();
+            let goal = goals.iter().find(|goal| goal.link_id() == goal_id);
+            goal.map(|goal| goal.name.as_str())
+        };
+        let check_ins = merged
+            .goal_entries
+            .typed()
+            .unwrap()
+            .iter()
+            .map(|entry| (goal_name(entry.goal_id), entry.day))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            check_ins,
+            [
+                (Some("Exercise"), 1),
+                (Some("Exercise"), 2),
+                (Some("Exercise"), 3),
+                (Some("Read"), 2)
+            ]
+        );
+        let weeks = merged
+            .goal_success_weeks
+            .typed()
+            .unwrap()
+            .iter()
+            .map(|week| (goal_name(week.goal_id), week.week))
+            .collect::<Vec<_>>();
+        assert_eq!(weeks, [(Some("Exercise"), 17)]);
+        assert!(merged
+            .goals
+            .typed()
+            .unwrap()
+            .iter()
+            .all(|goal| goal.goal_id == Some(goal.id)));
+        Ok(())
+    }
+
+    #[test]
+    /// Goals with other field names than expected are kept from the first file, with the same ids
+    fn merge_unknown_goals() -> Result<()> {
+        let json = std::fs::read_to_string("tests/data/synthetic/goals.json")?;
+        let json = json.replace("\"goalId\"", "\"goal_id\"");
+        let path = std::env::temp_dir().join(format!(
+            "daylio_tools_unknown_goals_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, &json)?;
+        let phone = load_daylio_json(&path);
+        std::fs::remove_file(&path)?;
+        let phone = phone?;
+        assert!(phone.goals.typed().is_some());
+        assert!(phone.goal_entries.typed().is_none());
+
+        let tablet = load_daylio_json("tests/data/synthetic/goals.json".as_ref())?;
+        let names = |daylio: &Daylio| {
+            let goals = daylio.goals.typed().unwrap().iter();
+            goals.map(|goal| goal.name.clone()).collect::<Vec<_>>()
+        };
+        let merged = merge(tablet.clone(), phone.clone());
+        assert_eq!(names(&merged), names(&tablet));
+        assert_eq!(merged.goal_entries.typed().unwrap().len(), 3);
+
+        // the check-ins cannot be followed, so the ids of the goals are not changed
+        let merged = merge(phone.clone(), tablet);
+        let ids = |daylio: &Daylio| {
+            let goals = daylio.goals.typed().unwrap().iter();
+            goals.map(Goal::link_id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&merged), ids(&phone));
+        assert_eq!(merged.goal_entries, phone.goal_entries);
+        assert_eq!(merged.goal_success_weeks, phone.goal_success_weeks);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_tag_groups() {
+        let input1 = input1();
+
+        let input2 = Daylio {
+            tag_groups: vec![
+                TagGroup {
+                    id: 1,
+                    name: "Work".to_owned(),
+                    is_expanded: true,
+                    order: 1,
+                    ..Default::default()
+                },
+                TagGroup {
+                    id: 2,
+                    name: "default".to_owned(),
+                    is_expanded: false,
+                    order: 2,
+                    ..Default::default()
+                },
+            ],
+            tags: vec![
+                Tag {
+                    id: 1,
+                    name: "meeting".to_owned(),
+                    created_at: 1651129353712,
+                    id_tag_group: 1,
+                    ..Default::default()
+                },
+                Tag {
+                    id: 2,
+                    name: "tag1".to_owned(),
+                    created_at: 1651129353713,
+                    id_tag_group: 2,
+                    ..Default::default()
+                },
+            ],
+            ..Daylio::default()
+        };
+
+        let merged = merge(input1, input2);
+
+        let groups = merged
+            .tag_groups
+            .iter()
+            .map(|group| (group.id, group.name.as_str(), group.order))
+            .collect::<Vec<_>>();
+        assert_eq!(groups, [(1, "Default", 1), (2, "Work", 2)]);
+
+        let tags = merged
+            .tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.id_tag_group))
+            .collect::<Vec<_>>();
+        assert_eq!(tags, [("tag1", 1), ("tag2", 2), ("meeting", 2)]);
+    }
+
+    /// The second input has the same entries, but the first one has been edited
+    fn conflicting_inputs() -> (Daylio, Daylio) {
+        let mut input1 = input1();
+        input1.metadata.created_at = 1;
+
+        let mut input2 = input1.clone();
+        input2.metadata.created_at = 2;
+        input2.day_entries[2].note = "2, edited".to_owned();
+        input2.day_entries[2].tags = vec![28];
+
+        (input1, input2)
+    }
+
+    fn notes_and_tags(daylio: &Daylio) -> Vec<(&str, Vec<i64>)> {
+        daylio
+            .day_entries
+            .iter()
+            .map(|entry| (entry.note.as_str(), entry.tags.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_strategies() {
+        let (input1, input2) = conflicting_inputs();
+        let (merged, conflicts) = merge_with_strategy(input1, input2, MergeStrategy::KeepBoth);
+        assert_eq!(merged.day_entries.len(), 4);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].datetime, 1659386700000);
+        assert_eq!(conflicts[0].fields, ["note", "tags"]);
+        assert_eq!(conflicts[0].resolution, Resolution::KeepBoth);
+
+        let (input1, input2) = conflicting_inputs();
+        let (merged, _) = merge_with_strategy(input1, input2, MergeStrategy::PreferFirst);
+        assert_eq!(
+            notes_and_tags(&merged),
+            [("", vec![1]), ("1", vec![2]), ("2", vec![1, 2])]
+        );
+
+        let (input1, input2) = conflicting_inputs();
+        let (merged, _) = merge_with_strategy(input1, input2, MergeStrategy::PreferNewest);
+        assert_eq!(
+            notes_and_tags(&merged),
+            [("", vec![1]), ("1", vec![2]), ("2, edited", vec![2])]
+        );
+
+        let (input1, input2) = conflicting_inputs();
+        let (merged, _) = merge_with_strategy(input1, input2, MergeStrategy::Union);
+        assert_eq!(
+            notes_and_tags(&merged),
+            [
+                ("", vec![1]),
+                ("1", vec![2]),
+                ("2\n\n2, edited", vec![1, 2])
+            ]
+        );
+
+        let (input1, input2) = conflicting_inputs();
+        let mut asked = 0;
+        let mut ask = |_: &Daylio, first: Option<&DayEntry>, second: Option<&DayEntry>| {
+            asked += 1;
+            assert_eq!(first.unwrap().note, "2");
+            assert_eq!(second.unwrap().note, "2, edited");
+            Resolution::KeepSecond
+        };
+        let (merged, conflicts) =
+            merge_with_strategy(input1, input2, MergeStrategy::Interactive(&mut ask));
+        assert_eq!(asked, 1);
+        assert_eq!(conflicts[0].resolution, Resolution::KeepSecond);
+        assert_eq!(merged.day_entries[2].note, "2, edited");
+    }
+
+    #[test]
+    fn merge_report() {
+        let (input1, mut input2) = conflicting_inputs();
+        input2.tags[0].name = "TAG1".to_owned();
+        input2.tags[1].name = "tag3".to_owned();
+
+        let (_, report) = merge_with_report(input1, input2, MergeStrategy::KeepBoth);
+
+        assert_eq!(report.first_entries, 3);
+        assert_eq!(report.second_entries, 3);
+        assert_eq!(report.added_entries, [1659386700000, 1659463200000]);
+        assert_eq!(report.deduplicated_entries, [1659481200000]);
+        assert_eq!(report.conflicts.len(), 2);
+
+        let custom_mood = report.moods.iter().find(|x| x.name == "custom").unwrap();
+        assert!(custom_mood.is_unified());
+
+        assert_eq!(
+            report.tags,
+            [
+                IdMapping {
+                    name: "tag1".to_owned(),
+                    first_id: Some(24),
+                    second_id: Some(24),
+                    new_id: 1,
+                },
+                IdMapping {
+                    name: "tag2".to_owned(),
+                    first_id: Some(28),
+                    second_id: None,
+                    new_id: 2,
+                },
+                IdMapping {
+                    name: "tag3".to_owned(),
+                    first_id: None,
+                    second_id: Some(28),
+                    new_id: 3,
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use color_eyre::Result;
+    use similar_asserts::assert_eq;
+
+    use daylio_tools::{
+        backup_existing, Daylio, ensure_not_input, load_daylio_backup, load_daylio_json,
+        store_daylio_backup, store_daylio_json,
+    };
+
+    fn temp_dir(name: &str) -> Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("daylio_tools_{name}_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn store_replaces_existing_file() -> Result<()> {
+        let dir = temp_dir("store")?;
+        let path = dir.join("out.daylio");
+        std::fs::write(&path, "not a backup")?;
+
+        let daylio = load_daylio_backup("tests/data/old.daylio".as_ref())?;
+        store_daylio_backup(&daylio, &path)?;
+
+        assert_eq!(load_daylio_backup(&path)?, daylio);
+        // the temporary file has been renamed
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn failed_store_keeps_existing_file() -> Result<()> {
+        let dir = temp_dir("failed_store")?;
+        let path = dir.join("out.json");
+        std::fs::write(&path, "previous")?;
+
+        // a directory cannot be replaced by a file
+        assert!(store_daylio_json(&Daylio::default(), &dir).is_err());
+        assert_eq!(std::fs::read_to_string(&path)?, "previous");
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Fields added by newer versions of the app are written back unchanged
+    fn round_trip_keeps_unknown_fields() -> Result<()> {
+        let dir = temp_dir("round_trip")?;
+        let expected = std::fs::read_to_string("tests/data/newer_version.json")?;
+
+        let daylio = load_daylio_json("tests/data/newer_version.json".as_ref())?;
+        let path = dir.join("out.json");
+        store_daylio_json(&daylio, &path)?;
+        assert_eq!(std::fs::read_to_string(&path)?, expected);
+
+        let path = dir.join("out.daylio");
+        store_daylio_backup(&daylio, &path)?;
+        let daylio = load_daylio_backup(&path)?;
+        assert_eq!(serde_json::to_string_pretty(&daylio)?, expected);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    /// Versions that no migration leads to are refused before their fields are misread
+    fn unknown_version_is_refused() -> Result<()> {
+        let dir = temp_dir("unknown_version")?;
+        let json = std::fs::read_to_string("tests/data/newer_version.json")?;
+        let json = json.replacen("\"version\": 15", "\"version\": 99", 1);
+        assert!(json.contains("\"version\": 99"));
+        let path = dir.join("future.json");
+        std::fs::write(&path, json)?;
+
+        let error = load_daylio_json(&path).unwrap_err().to_string();
+        assert!(error.starts_with("Backups of version 99 are not supported"));
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn output_is_an_input() -> Result<()> {
+        let inputs = vec![
+            PathBuf::from("tests/data/old.daylio"),
+            PathBuf::from("tests/data/new.daylio"),
+        ];
+
+        assert!(ensure_not_input("tests/data/../data/new.daylio".as_ref(), &inputs).is_err());
+        assert!(ensure_not_input("./tests/data/old.daylio".as_ref(), &inputs).is_err());
+        assert!(ensure_not_input("tests/data/out.daylio".as_ref(), &inputs).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn output_is_a_hard_link_to_an_input() -> Result<()> {
+        let dir = temp_dir("hard_link")?;
+        let input = dir.join("input.daylio");
+        let link = dir.join("link.daylio");
+        std::fs::copy("tests/data/old.daylio", &input)?;
+        std::fs::hard_link(&input, &link)?;
+
+        let result = ensure_not_input(&link, std::slice::from_ref(&input));
+
+        std::fs::remove_dir_all(dir)?;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn backup_existing_file() -> Result<()> {
+        let dir = temp_dir("bak")?;
+        let path = dir.join("out.json");
+
+        assert_eq!(backup_existing(&path)?, None);
+
+        std::fs::write(&path, "previous")?;
+        let bak = backup_existing(&path)?.expect("the file exists");
+
+        let name = bak.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("out.json.") && name.ends_with(".bak"));
+        assert_eq!(std::fs::read_to_string(bak)?, "previous");
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}